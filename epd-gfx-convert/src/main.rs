@@ -0,0 +1,288 @@
+#![deny(clippy::all)]
+#![forbid(unsafe_code)]
+
+//! Convert PNG and SVG files into the native epd-gfx image format, either as
+//! a binary file for `include_bytes!` or as a Rust `const` array.
+
+use clap::{Parser, ValueEnum};
+use epd_gfx::image::{encode, Compression};
+use resvg::{tiny_skia, usvg};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Parser)]
+#[command(about = "Convert PNG/SVG images into the epd-gfx image format")]
+struct Args {
+    /// The PNG or SVG file to convert.
+    input: PathBuf,
+
+    /// Where to write the result. Defaults to the input with an `.epdi`
+    /// (binary) or `.rs` (Rust) extension.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output format. Guessed from the output extension if not given.
+    #[arg(short, long, value_enum)]
+    format: Option<Format>,
+
+    /// Bits per pixel of the stored image: 1, 2, 4 or 8.
+    #[arg(short, long, default_value_t = 4)]
+    bpp: u8,
+
+    /// Compression of the pixel data.
+    #[arg(short, long, value_enum, default_value_t = CompressionArg::Rle)]
+    compression: CompressionArg,
+
+    /// Store pixels with less than 50% alpha with this value and mark it as
+    /// the transparent color. Without it, transparent pixels become white.
+    #[arg(short, long)]
+    transparent: Option<u8>,
+
+    /// Width to render SVG files at. Keeps the aspect ratio if only one of
+    /// width and height is given.
+    #[arg(long)]
+    width: Option<u32>,
+
+    /// Height to render SVG files at.
+    #[arg(long)]
+    height: Option<u32>,
+
+    /// Name of the generated Rust constant. Defaults to the input file name.
+    #[arg(short, long)]
+    name: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Binary image, to be embedded with `include_bytes!`.
+    Binary,
+    /// Rust source with a `const` byte array.
+    Rust,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CompressionArg {
+    None,
+    Rle,
+    Zlib,
+}
+
+impl From<CompressionArg> for Compression {
+    fn from(arg: CompressionArg) -> Self {
+        match arg {
+            CompressionArg::None => Compression::None,
+            CompressionArg::Rle => Compression::Rle,
+            CompressionArg::Zlib => Compression::Zlib,
+        }
+    }
+}
+
+/// An image as 8 bit luma and alpha values.
+struct GrayAlpha {
+    width: u32,
+    height: u32,
+    pixels: Vec<(u8, u8)>,
+}
+
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114 + 500) / 1000) as u8
+}
+
+fn load_png(path: &Path) -> Result<GrayAlpha, Box<dyn Error>> {
+    let mut decoder = png::Decoder::new(fs::File::open(path)?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+    let data = &buf[..info.buffer_size()];
+
+    let pixels = match info.color_type {
+        png::ColorType::Grayscale => data.iter().map(|&v| (v, 0xFF)).collect(),
+        png::ColorType::GrayscaleAlpha => data.chunks_exact(2).map(|p| (p[0], p[1])).collect(),
+        png::ColorType::Rgb => data
+            .chunks_exact(3)
+            .map(|p| (luma(p[0], p[1], p[2]), 0xFF))
+            .collect(),
+        png::ColorType::Rgba => data
+            .chunks_exact(4)
+            .map(|p| (luma(p[0], p[1], p[2]), p[3]))
+            .collect(),
+        png::ColorType::Indexed => return Err("indexed PNG was not expanded".into()),
+    };
+    Ok(GrayAlpha {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+fn load_svg(
+    path: &Path,
+    width: Option<u32>,
+    height: Option<u32>,
+) -> Result<GrayAlpha, Box<dyn Error>> {
+    let tree = usvg::Tree::from_data(&fs::read(path)?, &usvg::Options::default())?;
+    let size = tree.size();
+    let (width, height) = match (width, height) {
+        (Some(w), Some(h)) => (w, h),
+        (Some(w), None) => (w, (w as f32 * size.height() / size.width()).round() as u32),
+        (None, Some(h)) => ((h as f32 * size.width() / size.height()).round() as u32, h),
+        (None, None) => (size.width().ceil() as u32, size.height().ceil() as u32),
+    };
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).ok_or("invalid SVG render size")?;
+    let transform = tiny_skia::Transform::from_scale(
+        width as f32 / size.width(),
+        height as f32 / size.height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .map(|p| {
+            let c = p.demultiply();
+            (luma(c.red(), c.green(), c.blue()), c.alpha())
+        })
+        .collect();
+    Ok(GrayAlpha {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Quantize to `bpp` bits, compositing onto white or mapping transparent
+/// pixels to the color key. Opaque pixels of the key's color are moved to the
+/// closest other level, so that they stay visible.
+fn quantize(image: &GrayAlpha, bpp: u8, transparent: Option<u8>) -> Vec<u8> {
+    let max = (1u32 << bpp) - 1;
+    image
+        .pixels
+        .iter()
+        .map(|&(luma, alpha)| match transparent {
+            Some(key) if alpha < 0x80 => key,
+            Some(key) => {
+                let level = ((luma as u32 * max + 127) / 255) as u8;
+                let brighter = luma as u32 * max > key as u32 * 255;
+                if level != key {
+                    level
+                } else if key == 0 || (brighter && (key as u32) < max) {
+                    key + 1
+                } else {
+                    key - 1
+                }
+            }
+            None => {
+                let blended = (luma as u32 * alpha as u32 + 0xFF * (0xFF - alpha as u32)) / 0xFF;
+                ((blended * max + 127) / 255) as u8
+            }
+        })
+        .collect()
+}
+
+fn const_name(args: &Args) -> String {
+    match &args.name {
+        Some(name) => name.clone(),
+        None => {
+            let name: String = args
+                .input
+                .file_stem()
+                .map(|s| s.to_string_lossy())
+                .unwrap_or_default()
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() {
+                        c.to_ascii_uppercase()
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            // Identifiers can not start with a digit, e.g. for `1.png`
+            if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+                format!("IMG_{name}")
+            } else {
+                name
+            }
+        }
+    }
+}
+
+fn to_rust(name: &str, source: &Path, data: &[u8]) -> String {
+    let mut out = format!(
+        "// Generated by epd-gfx-convert from {}\npub const {}: [u8; {}] = [\n",
+        source.display(),
+        name,
+        data.len()
+    );
+    for line in data.chunks(16) {
+        let bytes: Vec<String> = line.iter().map(|b| format!("0x{b:02x},")).collect();
+        out.push_str(&format!("    {}\n", bytes.join(" ")));
+    }
+    out.push_str("];\n");
+    out
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+
+    if !matches!(args.bpp, 1 | 2 | 4 | 8) {
+        return Err(format!("unsupported bit depth {}", args.bpp).into());
+    }
+    if let Some(key) = args.transparent {
+        if key as u32 >= 1 << args.bpp {
+            return Err(format!(
+                "transparent color {key} does not fit into {} bits",
+                args.bpp
+            )
+            .into());
+        }
+    }
+
+    let extension = args
+        .input
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase());
+    let image = match extension.as_deref() {
+        Some("png") => load_png(&args.input)?,
+        Some("svg") => load_svg(&args.input, args.width, args.height)?,
+        _ => return Err("input must be a .png or .svg file".into()),
+    };
+    if image.width > u16::MAX as u32 || image.height > u16::MAX as u32 {
+        return Err("image is too large".into());
+    }
+
+    let pixels = quantize(&image, args.bpp, args.transparent);
+    let data = encode(
+        image.width,
+        image.height,
+        args.bpp,
+        &pixels,
+        args.transparent,
+        args.compression.into(),
+    );
+
+    let format = args.format.unwrap_or_else(|| match &args.output {
+        Some(path) if path.extension().is_some_and(|e| e == "rs") => Format::Rust,
+        _ => Format::Binary,
+    });
+    let output = args.output.clone().unwrap_or_else(|| match format {
+        Format::Binary => args.input.with_extension("epdi"),
+        Format::Rust => args.input.with_extension("rs"),
+    });
+    match format {
+        Format::Binary => fs::write(&output, &data)?,
+        Format::Rust => fs::write(&output, to_rust(&const_name(&args), &args.input, &data))?,
+    }
+    println!(
+        "{} -> {}: {}x{}, {} bpp, {} bytes",
+        args.input.display(),
+        output.display(),
+        image.width,
+        image.height,
+        args.bpp,
+        data.len()
+    );
+    Ok(())
+}
@@ -1,6 +1,8 @@
 #![deny(clippy::all)]
 #![forbid(unsafe_code)]
 
+use epd_gfx::render::{render_rgba8, Rotation};
+use epd_gfx::Gray4;
 use log::error;
 use pixels::{Error, Pixels, SurfaceTexture};
 use winit::dpi::LogicalSize;
@@ -109,11 +111,11 @@ impl World {
     }
 
     fn update(&mut self) {
-        epd_gfx::set_all(&mut self.fb, 0xFF);
-        epd_gfx::fill_rect(&mut self.fb, 50, 75, 400, 250, 0x00);
-        epd_gfx::fill_rect(&mut self.fb, 200, 150, 400, 250, 0x88);
-        epd_gfx::fill_rect(&mut self.fb, 50, 700, 400, 200, 0x00);
-        epd_gfx::fill_rect(&mut self.fb, 75, 701, 400, 200, 0x00);
+        epd_gfx::set_all(&mut self.fb, Gray4::WHITE);
+        epd_gfx::fill_rect(&mut self.fb, 50, 75, 400, 250, Gray4::BLACK);
+        epd_gfx::fill_rect(&mut self.fb, 200, 150, 400, 250, Gray4::GRAY);
+        epd_gfx::fill_rect(&mut self.fb, 50, 700, 400, 200, Gray4::BLACK);
+        epd_gfx::fill_rect(&mut self.fb, 75, 701, 400, 200, Gray4::BLACK);
         epd_gfx::font::draw_text(&mut self.fb, 0, 0, "Hello from RustType!", 240);
     }
 
@@ -121,32 +123,11 @@ impl World {
     ///
     /// Assumes the default texture format: `wgpu::TextureFormat::Rgba8UnormSrgb`
     fn draw(&self, frame: &mut [u8]) {
-        for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
-            let mut x = (i % WIDTH as usize) as u32;
-            let mut y = (i / WIDTH as usize) as u32;
-
-            let fb_index = if ROTATED {
-                x = (i / HEIGHT as usize) as u32;
-                y = (i % HEIGHT as usize) as u32;
-                x = WIDTH - x - 1;
-                (y * WIDTH + x) / 2
-            } else {
-                (y * WIDTH + x) / 2
-            };
-
-            let fb_byte = self.fb[fb_index as usize];
-
-            let shade = {
-                if x % 2 == 0 {
-                    (fb_byte & 0xF0) >> 4
-                } else {
-                    fb_byte & 0x0F
-                }
-            };
-
-            // Scale range from 4 bits to 1 byte (0-255).
-            let rgba = [shade * 17, shade * 17, shade * 17, 0xff];
-            pixel.copy_from_slice(&rgba);
-        }
+        let rotation = if ROTATED {
+            Rotation::Portrait
+        } else {
+            Rotation::Landscape
+        };
+        render_rgba8(&self.fb, rotation, frame);
     }
 }
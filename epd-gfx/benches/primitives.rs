@@ -0,0 +1,87 @@
+//! Compare the byte-wise primitives with drawing pixel by pixel, which is how
+//! every primitive used to work.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use epd_gfx::mono::Mono;
+use epd_gfx::{draw_pixel, Framebuffer, Gray4, EPD_HEIGHT, EPD_WIDTH};
+
+const FB_SIZE: usize = (EPD_WIDTH * EPD_HEIGHT / 2) as usize;
+
+fn pixel_fill_rect<F: Framebuffer + ?Sized>(
+    fb: &mut F,
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+    color: Gray4,
+) {
+    for yy in y..y + h {
+        for xx in x..x + w {
+            draw_pixel(fb, xx, yy, color);
+        }
+    }
+}
+
+fn fill_rect(c: &mut Criterion) {
+    let mut fb = vec![0xFF; FB_SIZE];
+    let mut group = c.benchmark_group("fill_rect full screen");
+    group.bench_function("per pixel", |b| {
+        b.iter(|| pixel_fill_rect(&mut fb, 0, 0, EPD_HEIGHT, EPD_WIDTH, black_box(Gray4::GRAY)))
+    });
+    group.bench_function("byte-wise", |b| {
+        b.iter(|| epd_gfx::fill_rect(&mut fb, 0, 0, EPD_HEIGHT, EPD_WIDTH, black_box(Gray4::GRAY)))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("fill_rect odd edges");
+    group.bench_function("per pixel", |b| {
+        b.iter(|| pixel_fill_rect(&mut fb, 13, 27, 401, 333, black_box(Gray4::new(0x3))))
+    });
+    group.bench_function("byte-wise", |b| {
+        b.iter(|| epd_gfx::fill_rect(&mut fb, 13, 27, 401, 333, black_box(Gray4::new(0x3))))
+    });
+    group.finish();
+}
+
+fn lines(c: &mut Criterion) {
+    let mut fb = vec![0xFF; FB_SIZE];
+    let mut group = c.benchmark_group("lines");
+    group.bench_function("vline per pixel", |b| {
+        b.iter(|| pixel_fill_rect(&mut fb, 100, 0, 1, EPD_WIDTH, black_box(Gray4::BLACK)))
+    });
+    group.bench_function("vline byte-wise", |b| {
+        b.iter(|| epd_gfx::draw_vline(&mut fb, 100, 0, EPD_WIDTH, black_box(Gray4::BLACK)))
+    });
+    group.bench_function("hline per pixel", |b| {
+        b.iter(|| pixel_fill_rect(&mut fb, 0, 100, EPD_HEIGHT, 1, black_box(Gray4::BLACK)))
+    });
+    group.bench_function("hline byte-wise", |b| {
+        b.iter(|| epd_gfx::draw_hline(&mut fb, 0, 100, EPD_HEIGHT, black_box(Gray4::BLACK)))
+    });
+    group.finish();
+}
+
+fn clear(c: &mut Criterion) {
+    let mut fb = vec![0xFF; FB_SIZE];
+    let mut mono = Mono::new();
+    let mut group = c.benchmark_group("clear");
+    group.bench_function("4bpp set_all", |b| {
+        b.iter(|| epd_gfx::set_all(&mut fb, black_box(Gray4::WHITE)))
+    });
+    group.bench_function("1bpp fill_rect", |b| {
+        b.iter(|| {
+            epd_gfx::fill_rect(
+                &mut mono,
+                0,
+                0,
+                EPD_HEIGHT,
+                EPD_WIDTH,
+                black_box(Gray4::WHITE),
+            )
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, fill_rect, lines, clear);
+criterion_main!(benches);
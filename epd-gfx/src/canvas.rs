@@ -0,0 +1,514 @@
+//! A stateful drawing context on top of a framebuffer, similar to an HTML
+//! canvas or a Cairo context.
+//!
+//! Coordinates are `f32` in user space, which the current [`Transform`] maps
+//! to the (portrait) coordinates of the framebuffer. A pixel is covered by a
+//! shape if its center lies inside of it.
+
+use crate::font::{self, Font, Scale};
+use crate::paint::Paint;
+use crate::{Framebuffer, Gray4};
+
+/// A scale followed by a translation, mapping user space to framebuffer
+/// coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub scale_x: f32,
+    pub scale_y: f32,
+    pub translate_x: f32,
+    pub translate_y: f32,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        scale_x: 1.0,
+        scale_y: 1.0,
+        translate_x: 0.0,
+        translate_y: 0.0,
+    };
+
+    /// Map a point from user space to framebuffer coordinates.
+    /// ```
+    /// use epd_gfx::canvas::Transform;
+    /// let t = Transform::IDENTITY.translate(10.0, 20.0).scale(2.0, 3.0);
+    /// assert_eq!(t.apply(1.0, 1.0), (12.0, 23.0));
+    /// ```
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            x * self.scale_x + self.translate_x,
+            y * self.scale_y + self.translate_y,
+        )
+    }
+
+    /// Move the origin of user space by (dx, dy), measured in user space.
+    pub fn translate(self, dx: f32, dy: f32) -> Transform {
+        let (translate_x, translate_y) = self.apply(dx, dy);
+        Transform {
+            translate_x,
+            translate_y,
+            ..self
+        }
+    }
+
+    /// Scale user space around its origin.
+    pub fn scale(self, sx: f32, sy: f32) -> Transform {
+        Transform {
+            scale_x: self.scale_x * sx,
+            scale_y: self.scale_y * sy,
+            ..self
+        }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Everything [`Canvas::save`] saves and [`Canvas::restore`] restores.
+#[derive(Debug, Clone)]
+struct State {
+    fill: Paint,
+    stroke: Gray4,
+    stroke_width: f32,
+    font: Font<'static>,
+    text_size: f32,
+    transform: Transform,
+}
+
+/// A framebuffer together with the current drawing state.
+///
+/// The canvas owns the framebuffer, pass `&mut fb` to draw into a borrowed
+/// one.
+/// ```
+/// use epd_gfx::canvas::Canvas;
+/// use epd_gfx::Gray4;
+///
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// let mut canvas = Canvas::new(&mut fb);
+/// canvas.translate(100.0, 100.0);
+/// canvas.set_fill(Gray4::BLACK);
+/// canvas.fill_rect(0.0, 0.0, 10.0, 10.0);
+///
+/// canvas.save();
+/// canvas.scale(2.0, 2.0);
+/// canvas.set_fill(Gray4::GRAY);
+/// canvas.fill_rect(10.0, 0.0, 10.0, 10.0);
+/// canvas.restore();
+///
+/// canvas.fill_rect(0.0, 20.0, 1.0, 1.0);
+/// assert_eq!(epd_gfx::get_pixel(&fb, 109, 109), Some(Gray4::BLACK));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 139, 119), Some(Gray4::GRAY));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 100, 120), Some(Gray4::BLACK));
+/// ```
+#[derive(Debug)]
+pub struct Canvas<F> {
+    fb: F,
+    state: State,
+    saved: Vec<State>,
+}
+
+impl<F: Framebuffer> Canvas<F> {
+    /// Start drawing black on `fb`, with a 1 pixel wide stroke and the
+    /// built-in font at 16 pixels.
+    pub fn new(fb: F) -> Self {
+        Self {
+            fb,
+            state: State {
+                fill: Paint::Solid(Gray4::BLACK),
+                stroke: Gray4::BLACK,
+                stroke_width: 1.0,
+                font: font::karla(),
+                text_size: 16.0,
+                transform: Transform::IDENTITY,
+            },
+            saved: Vec::new(),
+        }
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.fb
+    }
+
+    pub fn inner_mut(&mut self) -> &mut F {
+        &mut self.fb
+    }
+
+    pub fn into_inner(self) -> F {
+        self.fb
+    }
+
+    /// Push the current drawing state, including the transform, onto a stack.
+    pub fn save(&mut self) {
+        self.saved.push(self.state.clone());
+    }
+
+    /// Return to the drawing state of the matching [`Canvas::save`]. Does
+    /// nothing if no state was saved.
+    pub fn restore(&mut self) {
+        if let Some(state) = self.saved.pop() {
+            self.state = state;
+        }
+    }
+
+    pub fn fill(&self) -> &Paint {
+        &self.state.fill
+    }
+
+    /// Fill shapes and text with a single color.
+    pub fn set_fill(&mut self, color: Gray4) {
+        self.state.fill = Paint::Solid(color);
+    }
+
+    /// Fill shapes and text with a gradient or pattern, given in user space.
+    /// ```
+    /// use epd_gfx::canvas::Canvas;
+    /// use epd_gfx::paint::Gradient;
+    /// use epd_gfx::Gray4;
+    ///
+    /// let mut fb = vec![0xFF; 960 * 540 / 2];
+    /// let mut canvas = Canvas::new(&mut fb);
+    /// canvas.translate(100.0, 0.0);
+    /// canvas.set_fill_paint(Gradient::linear((0.0, 0.0), (16.0, 0.0), Gray4::BLACK, Gray4::WHITE));
+    /// canvas.fill_circle(10.0, 10.0, 10.0);
+    /// assert_eq!(epd_gfx::get_pixel(&fb, 100, 10), Some(Gray4::BLACK));
+    /// assert_eq!(epd_gfx::get_pixel(&fb, 110, 10), Some(Gray4::new(0xA)));
+    /// assert_eq!(epd_gfx::get_pixel(&fb, 119, 10), Some(Gray4::WHITE));
+    /// ```
+    pub fn set_fill_paint(&mut self, paint: impl Into<Paint>) {
+        self.state.fill = paint.into();
+    }
+
+    pub fn stroke(&self) -> Gray4 {
+        self.state.stroke
+    }
+
+    /// Set the color of lines and outlines.
+    pub fn set_stroke(&mut self, color: Gray4) {
+        self.state.stroke = color;
+    }
+
+    pub fn stroke_width(&self) -> f32 {
+        self.state.stroke_width
+    }
+
+    /// Set the width of lines and outlines in user space.
+    pub fn set_stroke_width(&mut self, width: f32) {
+        self.state.stroke_width = width.max(0.0);
+    }
+
+    pub fn font(&self) -> &Font<'static> {
+        &self.state.font
+    }
+
+    pub fn set_font(&mut self, font: Font<'static>) {
+        self.state.font = font;
+    }
+
+    pub fn text_size(&self) -> f32 {
+        self.state.text_size
+    }
+
+    /// Set the height of text in user space.
+    pub fn set_text_size(&mut self, size: f32) {
+        self.state.text_size = size.max(0.0);
+    }
+
+    pub fn transform(&self) -> Transform {
+        self.state.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.state.transform = transform;
+    }
+
+    pub fn reset_transform(&mut self) {
+        self.state.transform = Transform::IDENTITY;
+    }
+
+    /// Move the origin of user space, see [`Transform::translate`].
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        self.state.transform = self.state.transform.translate(dx, dy);
+    }
+
+    /// Scale user space, see [`Transform::scale`].
+    pub fn scale(&mut self, sx: f32, sy: f32) {
+        self.state.transform = self.state.transform.scale(sx, sy);
+    }
+
+    /// Fill the whole framebuffer with `color`, ignoring the transform.
+    pub fn clear(&mut self, color: Gray4) {
+        let (w, h) = (self.fb.width(), self.fb.height());
+        self.fb.fill_rect(0, 0, w, h, color);
+    }
+
+    /// Fill a rectangle with the fill paint.
+    pub fn fill_rect(&mut self, x: f32, y: f32, w: f32, h: f32) {
+        let paint = self.fill_paint();
+        self.device_rect(x, y, x + w, y + h, &paint);
+    }
+
+    /// Draw the outline of a rectangle with the stroke color. The stroke is
+    /// centered on the edges of the rectangle.
+    /// ```
+    /// use epd_gfx::canvas::Canvas;
+    /// use epd_gfx::Gray4;
+    ///
+    /// let mut fb = vec![0xFF; 960 * 540 / 2];
+    /// let mut canvas = Canvas::new(&mut fb);
+    /// canvas.set_stroke_width(2.0);
+    /// canvas.stroke_rect(10.0, 10.0, 20.0, 20.0);
+    /// assert_eq!(epd_gfx::get_pixel(&fb, 9, 9), Some(Gray4::BLACK));
+    /// assert_eq!(epd_gfx::get_pixel(&fb, 30, 20), Some(Gray4::BLACK));
+    /// assert_eq!(epd_gfx::get_pixel(&fb, 20, 20), Some(Gray4::WHITE));
+    /// ```
+    pub fn stroke_rect(&mut self, x: f32, y: f32, w: f32, h: f32) {
+        let (paint, half) = (self.stroke_paint(), self.state.stroke_width / 2.0);
+        let (x0, x1) = (x.min(x + w), x.max(x + w));
+        let (y0, y1) = (y.min(y + h), y.max(y + h));
+        // Top and bottom include the corners, left and right fit in between
+        self.device_rect(x0 - half, y0 - half, x1 + half, y0 + half, &paint);
+        self.device_rect(x0 - half, y1 - half, x1 + half, y1 + half, &paint);
+        self.device_rect(x0 - half, y0 + half, x0 + half, y1 - half, &paint);
+        self.device_rect(x1 - half, y0 + half, x1 + half, y1 - half, &paint);
+    }
+
+    /// Draw a line with the stroke color and width. The ends are extended
+    /// by half the stroke width.
+    /// ```
+    /// use epd_gfx::canvas::Canvas;
+    /// use epd_gfx::Gray4;
+    ///
+    /// let mut fb = vec![0xFF; 960 * 540 / 2];
+    /// let mut canvas = Canvas::new(&mut fb);
+    /// canvas.line(0.5, 0.5, 10.5, 10.5);
+    /// assert!((0..=10).all(|i| epd_gfx::get_pixel(&fb, i, i) == Some(Gray4::BLACK)));
+    /// assert_eq!(epd_gfx::get_pixel(&fb, 10, 0), Some(Gray4::WHITE));
+    /// ```
+    pub fn line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32) {
+        let quad = self.line_quad((x0, y0), (x1, y1));
+        let paint = self.stroke_paint();
+        self.device_polygon(&quad, &paint);
+    }
+
+    /// Fill a polygon with the fill paint, using the non-zero winding rule.
+    /// ```
+    /// use epd_gfx::canvas::Canvas;
+    /// use epd_gfx::Gray4;
+    ///
+    /// let mut fb = vec![0xFF; 960 * 540 / 2];
+    /// let mut canvas = Canvas::new(&mut fb);
+    /// canvas.fill_polygon(&[(0.0, 0.0), (20.0, 0.0), (0.0, 20.0)]);
+    /// assert_eq!(epd_gfx::get_pixel(&fb, 2, 2), Some(Gray4::BLACK));
+    /// assert_eq!(epd_gfx::get_pixel(&fb, 18, 18), Some(Gray4::WHITE));
+    /// ```
+    pub fn fill_polygon(&mut self, points: &[(f32, f32)]) {
+        let transform = self.state.transform;
+        let points: Vec<_> = points.iter().map(|&(x, y)| transform.apply(x, y)).collect();
+        let paint = self.fill_paint();
+        self.device_polygon(&points, &paint);
+    }
+
+    /// Draw lines through `points` with the stroke color, returning to the
+    /// first point if `closed` is set.
+    pub fn stroke_polygon(&mut self, points: &[(f32, f32)], closed: bool) {
+        let segments = points.windows(2).map(|pair| (pair[0], pair[1]));
+        let closing = match (points.first(), points.last()) {
+            (Some(&first), Some(&last)) if closed && points.len() > 2 => Some((last, first)),
+            _ => None,
+        };
+        for (from, to) in segments.chain(closing) {
+            self.line(from.0, from.1, to.0, to.1);
+        }
+    }
+
+    /// Fill a circle with the fill paint.
+    /// ```
+    /// use epd_gfx::canvas::Canvas;
+    /// use epd_gfx::Gray4;
+    ///
+    /// let mut fb = vec![0xFF; 960 * 540 / 2];
+    /// let mut canvas = Canvas::new(&mut fb);
+    /// canvas.fill_circle(50.0, 50.0, 10.0);
+    /// assert_eq!(epd_gfx::get_pixel(&fb, 50, 41), Some(Gray4::BLACK));
+    /// assert_eq!(epd_gfx::get_pixel(&fb, 41, 41), Some(Gray4::WHITE));
+    /// ```
+    pub fn fill_circle(&mut self, cx: f32, cy: f32, radius: f32) {
+        let paint = self.fill_paint();
+        self.device_ellipse(cx, cy, radius, 0.0, &paint);
+    }
+
+    /// Draw the outline of a circle with the stroke color. The stroke is
+    /// centered on the circle.
+    pub fn stroke_circle(&mut self, cx: f32, cy: f32, radius: f32) {
+        let (paint, half) = (self.stroke_paint(), self.state.stroke_width / 2.0);
+        self.device_ellipse(cx, cy, radius + half, (radius - half).max(0.0), &paint);
+    }
+
+    /// Draw text with the fill paint with its top left corner at (x, y).
+    /// ```
+    /// use epd_gfx::canvas::Canvas;
+    /// use epd_gfx::Gray4;
+    ///
+    /// let mut fb = vec![0xFF; 960 * 540 / 2];
+    /// let mut canvas = Canvas::new(&mut fb);
+    /// canvas.set_text_size(32.0);
+    /// canvas.fill_text("Hello", 10.0, 10.0);
+    /// let width = canvas.measure_text("Hello");
+    /// assert!(width > 50.0 && width < 100.0);
+    /// assert_eq!(epd_gfx::render::pixels(&fb, 0, 0, 10, 960).min(), Some(Gray4::WHITE));
+    /// assert_eq!(epd_gfx::render::pixels(&fb, 10, 10, 100, 40).min(), Some(Gray4::BLACK));
+    /// ```
+    pub fn fill_text(&mut self, text: &str, x: f32, y: f32) {
+        let transform = self.state.transform;
+        let (x, y) = transform.apply(x, y);
+        let scale = Scale {
+            x: self.state.text_size * transform.scale_x.abs(),
+            y: self.state.text_size * transform.scale_y.abs(),
+        };
+        let paint = self.fill_paint();
+        font::fill_text_with(
+            &mut self.fb,
+            &self.state.font,
+            x,
+            y,
+            text,
+            scale,
+            |px, py| paint.color_at(px, py),
+        );
+    }
+
+    /// The width of `text` in user space, if drawn with the current font and
+    /// text size.
+    pub fn measure_text(&self, text: &str) -> f32 {
+        font::text_width(&self.state.font, text, Scale::uniform(self.state.text_size))
+    }
+
+    /// The fill paint in framebuffer coordinates.
+    fn fill_paint(&self) -> Paint {
+        self.state.fill.transformed(&self.state.transform)
+    }
+
+    fn stroke_paint(&self) -> Paint {
+        Paint::Solid(self.state.stroke)
+    }
+
+    /// The corners of a line of the current stroke width, in framebuffer
+    /// coordinates.
+    fn line_quad(&self, from: (f32, f32), to: (f32, f32)) -> [(f32, f32); 4] {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        let half = self.state.stroke_width / 2.0;
+        // Unit vector along the line, scaled to half the stroke width
+        let (ux, uy) = if length > 0.0 {
+            (dx / length * half, dy / length * half)
+        } else {
+            (half, 0.0)
+        };
+        let t = self.state.transform;
+        [
+            t.apply(from.0 - ux - uy, from.1 - uy + ux),
+            t.apply(to.0 + ux - uy, to.1 + uy + ux),
+            t.apply(to.0 + ux + uy, to.1 + uy - ux),
+            t.apply(from.0 - ux + uy, from.1 - uy - ux),
+        ]
+    }
+
+    /// Fill the pixels with centers in `x0..x1` of framebuffer row `y`.
+    fn span(&mut self, x0: f32, x1: f32, y: u32, paint: &Paint) {
+        let start = (x0 - 0.5).ceil().max(0.0);
+        let end = (x1 - 0.5).ceil().min(self.fb.width() as f32);
+        if start < end {
+            paint.fill_span(&mut self.fb, start as u32, y, (end - start) as u32);
+        }
+    }
+
+    /// The framebuffer rows with centers in `y0..y1`.
+    fn rows(&self, y0: f32, y1: f32) -> std::ops::Range<u32> {
+        let start = (y0 - 0.5).ceil().max(0.0);
+        let end = (y1 - 0.5).ceil().min(self.fb.height() as f32);
+        if start < end {
+            start as u32..end as u32
+        } else {
+            0..0
+        }
+    }
+
+    /// Fill the rectangle between two corners given in user space.
+    fn device_rect(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, paint: &Paint) {
+        let t = self.state.transform;
+        let (ax, ay) = t.apply(x0, y0);
+        let (bx, by) = t.apply(x1, y1);
+        let (left, right) = (ax.min(bx), ax.max(bx));
+        for y in self.rows(ay.min(by), ay.max(by)) {
+            self.span(left, right, y, paint);
+        }
+    }
+
+    /// Fill the ellipse around a point given in user space, leaving out
+    /// the inner ellipse with radius `inner`.
+    fn device_ellipse(&mut self, cx: f32, cy: f32, outer: f32, inner: f32, paint: &Paint) {
+        let t = self.state.transform;
+        let (cx, cy) = t.apply(cx, cy);
+        let (sx, sy) = (t.scale_x.abs(), t.scale_y.abs());
+        let (outer_x, outer_y) = (outer * sx, outer * sy);
+        let (inner_x, inner_y) = (inner * sx, inner * sy);
+        // Half of the width of an ellipse at vertical distance dy
+        let half_width = |rx: f32, ry: f32, dy: f32| {
+            if ry <= 0.0 || dy.abs() >= ry {
+                None
+            } else {
+                Some(rx * (1.0 - (dy / ry).powi(2)).sqrt())
+            }
+        };
+        for y in self.rows(cy - outer_y, cy + outer_y) {
+            let dy = y as f32 + 0.5 - cy;
+            let Some(outer) = half_width(outer_x, outer_y, dy) else {
+                continue;
+            };
+            match half_width(inner_x, inner_y, dy) {
+                Some(inner) => {
+                    self.span(cx - outer, cx - inner, y, paint);
+                    self.span(cx + inner, cx + outer, y, paint);
+                }
+                None => self.span(cx - outer, cx + outer, y, paint),
+            }
+        }
+    }
+
+    /// Fill a polygon given in framebuffer coordinates.
+    fn device_polygon(&mut self, points: &[(f32, f32)], paint: &Paint) {
+        if points.len() < 3 {
+            return;
+        }
+        let top = points.iter().map(|p| p.1).fold(f32::INFINITY, f32::min);
+        let bottom = points.iter().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max);
+        let mut crossings: Vec<(f32, i32)> = Vec::new();
+        for y in self.rows(top, bottom) {
+            let center = y as f32 + 0.5;
+            crossings.clear();
+            for (i, &(x0, y0)) in points.iter().enumerate() {
+                let (x1, y1) = points[(i + 1) % points.len()];
+                // Half-open, so that shared vertices are only counted once
+                let direction = if y0 <= center && center < y1 {
+                    1
+                } else if y1 <= center && center < y0 {
+                    -1
+                } else {
+                    continue;
+                };
+                let x = x0 + (center - y0) / (y1 - y0) * (x1 - x0);
+                crossings.push((x, direction));
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                if winding != 0 {
+                    self.span(pair[0].0, pair[1].0, y, paint);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! Gray levels of the different framebuffer formats.
+//!
+//! All drawing functions take a [`Gray4`], one of the 16 gray levels of the
+//! display. [`Gray1`] and [`Gray8`] are the colors of the monochrome and the
+//! 8 bit framebuffers. Having a type for each keeps nibbles, packed bytes and
+//! 8 bit values apart.
+
+use std::fmt;
+
+/// One of the 16 gray levels of the display, from black (`0x0`) to white
+/// (`0xF`), as stored in a nibble of a 4bpp framebuffer.
+/// ```
+/// use epd_gfx::color::Gray4;
+/// assert_eq!(Gray4::new(0x8), Gray4::GRAY);
+/// assert_eq!(Gray4::from_luma(0xFF), Gray4::WHITE);
+/// assert_eq!(Gray4::from_rgb(255, 0, 0), Gray4::new(0x4));
+/// assert_eq!(Gray4::BLACK.invert(), Gray4::WHITE);
+/// assert_eq!(Gray4::GRAY.packed(), 0x88);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Gray4(u8);
+
+impl Gray4 {
+    pub const BLACK: Gray4 = Gray4(0x0);
+    pub const DARK_GRAY: Gray4 = Gray4(0x4);
+    pub const GRAY: Gray4 = Gray4(0x8);
+    pub const LIGHT_GRAY: Gray4 = Gray4(0xC);
+    pub const WHITE: Gray4 = Gray4(0xF);
+
+    /// All gray levels, from black to white.
+    pub const LEVELS: [Gray4; 16] = {
+        let mut levels = [Gray4(0); 16];
+        let mut i = 0;
+        while i < 16 {
+            levels[i] = Gray4(i as u8);
+            i += 1;
+        }
+        levels
+    };
+
+    /// The gray level `level`, which must be below 16.
+    pub const fn new(level: u8) -> Self {
+        assert!(level <= 0xF, "gray level out of range");
+        Gray4(level)
+    }
+
+    /// The gray level in the lower nibble of `byte`.
+    pub const fn from_low_nibble(byte: u8) -> Self {
+        Gray4(byte & 0x0F)
+    }
+
+    /// The gray level in the upper nibble of `byte`.
+    pub const fn from_high_nibble(byte: u8) -> Self {
+        Gray4(byte >> 4)
+    }
+
+    /// Quantize an 8 bit luma value to the nearest level.
+    pub const fn from_luma(luma: u8) -> Self {
+        Gray4(((luma as u16 * 15 + 127) / 255) as u8)
+    }
+
+    /// Convert an RGB color by its luma (ITU-R BT.601).
+    pub const fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114 + 500) / 1000;
+        Self::from_luma(luma as u8)
+    }
+
+    /// The nearest level to `level`, a value from 0.0 to 15.0.
+    pub fn from_level_f32(level: f32) -> Self {
+        Gray4(level.round().clamp(0.0, 15.0) as u8)
+    }
+
+    /// The level from 0 to 15.
+    pub const fn level(self) -> u8 {
+        self.0
+    }
+
+    /// Expand to 8 bits, so that white becomes `0xFF`.
+    pub const fn to_luma(self) -> u8 {
+        self.0 * 0x11
+    }
+
+    /// A byte holding this color in both nibbles, e.g. to fill two pixels of a
+    /// 4bpp framebuffer at once.
+    pub const fn packed(self) -> u8 {
+        self.0 * 0x11
+    }
+
+    pub const fn invert(self) -> Self {
+        Gray4(0xF - self.0)
+    }
+}
+
+impl Default for Gray4 {
+    /// White, like a cleared display.
+    fn default() -> Self {
+        Self::WHITE
+    }
+}
+
+impl fmt::Display for Gray4 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+impl From<Gray1> for Gray4 {
+    fn from(color: Gray1) -> Self {
+        if color.is_white() {
+            Gray4::WHITE
+        } else {
+            Gray4::BLACK
+        }
+    }
+}
+
+impl From<Gray8> for Gray4 {
+    /// Keep the upper 4 bits, like the 8 bit framebuffer does when it is
+    /// reduced to 4bpp.
+    fn from(color: Gray8) -> Self {
+        Gray4::from_high_nibble(color.0)
+    }
+}
+
+/// A pixel of the monochrome framebuffer, either black or white.
+/// ```
+/// use epd_gfx::color::{Gray1, Gray4};
+/// assert_eq!(Gray1::from_gray4(Gray4::new(0x7), Gray4::GRAY), Gray1::BLACK);
+/// assert_eq!(Gray4::from(Gray1::WHITE), Gray4::WHITE);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Gray1(bool);
+
+impl Gray1 {
+    pub const BLACK: Gray1 = Gray1(false);
+    pub const WHITE: Gray1 = Gray1(true);
+
+    /// White if `color` is at least `threshold`, black otherwise.
+    pub fn from_gray4(color: Gray4, threshold: Gray4) -> Self {
+        Gray1(color >= threshold)
+    }
+
+    pub const fn is_white(self) -> bool {
+        self.0
+    }
+
+    pub const fn invert(self) -> Self {
+        Gray1(!self.0)
+    }
+}
+
+impl Default for Gray1 {
+    fn default() -> Self {
+        Self::WHITE
+    }
+}
+
+/// An 8 bit gray value, from black (`0x00`) to white (`0xFF`).
+/// ```
+/// use epd_gfx::color::{Gray4, Gray8};
+/// assert_eq!(Gray8::from(Gray4::GRAY), Gray8::new(0x88));
+/// assert_eq!(Gray4::from(Gray8::new(0x42)), Gray4::new(0x4));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Gray8(u8);
+
+impl Gray8 {
+    pub const BLACK: Gray8 = Gray8(0x00);
+    pub const WHITE: Gray8 = Gray8(0xFF);
+
+    pub const fn new(luma: u8) -> Self {
+        Gray8(luma)
+    }
+
+    /// Convert an RGB color by its luma (ITU-R BT.601).
+    pub const fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114 + 500) / 1000;
+        Gray8(luma as u8)
+    }
+
+    pub const fn luma(self) -> u8 {
+        self.0
+    }
+
+    pub const fn invert(self) -> Self {
+        Gray8(0xFF - self.0)
+    }
+}
+
+impl Default for Gray8 {
+    fn default() -> Self {
+        Self::WHITE
+    }
+}
+
+impl From<Gray4> for Gray8 {
+    fn from(color: Gray4) -> Self {
+        Gray8(color.to_luma())
+    }
+}
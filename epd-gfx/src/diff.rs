@@ -0,0 +1,229 @@
+//! Comparing two 4bpp framebuffers, like epdiy does in
+//! `epd_difference_image_cropped` before each update of the high-level API.
+//!
+//! Everything here works in landscape (panel) coordinates, so the results can
+//! be used to plan calls to `epd_hl_update_area` or `epd_draw_base`.
+
+use crate::rect::{coalesce, Rect};
+use crate::{EPD_HEIGHT, EPD_WIDTH};
+
+/// Changed pixels in the same row which are at most this far apart end up in
+/// the same rectangle of [`changed_rects`].
+const SPAN_GAP: u32 = 8;
+
+/// Bytes per row of a 4bpp framebuffer.
+const ROW_BYTES: usize = (EPD_WIDTH / 2) as usize;
+
+/// Statistics about the changes within an area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DiffStats {
+    /// Number of pixels with a different color.
+    pub changed_pixels: u32,
+    /// Number of rows with at least one changed pixel.
+    pub changed_lines: u32,
+    /// The smallest rectangle containing all changed pixels.
+    pub bounds: Option<Rect>,
+    /// Whether all pixels of the area were white before, like
+    /// `previously_white` of `epd_difference_image_cropped`.
+    pub previously_white: bool,
+    /// Whether all pixels of the area were black before.
+    pub previously_black: bool,
+    /// Whether every changed pixel goes to black or white, so that a fast
+    /// monochrome mode like `MODE_DU` suffices.
+    pub to_black_white: bool,
+    /// Whether every changed pixel goes from black to white or vice versa.
+    pub only_black_white: bool,
+}
+
+impl DiffStats {
+    pub fn is_changed(&self) -> bool {
+        self.changed_pixels > 0
+    }
+}
+
+fn check_sizes(to: &[u8], from: &[u8]) {
+    let size = ROW_BYTES * EPD_HEIGHT as usize;
+    assert_eq!(to.len(), size, "invalid framebuffer size");
+    assert_eq!(from.len(), size, "invalid framebuffer size");
+}
+
+/// Call `visit(x, y, to, from)` for every changed pixel within `area`, row
+/// by row.
+fn for_each_change(to: &[u8], from: &[u8], area: Rect, mut visit: impl FnMut(u32, u32, u8, u8)) {
+    check_sizes(to, from);
+    let Some(area) = area.intersection(&Rect::landscape()) else {
+        return;
+    };
+    for y in area.y..area.bottom() {
+        let start = y as usize * ROW_BYTES;
+        let to_row = &to[start..start + ROW_BYTES];
+        let from_row = &from[start..start + ROW_BYTES];
+        let first = area.x as usize / 2;
+        let last = (area.right() as usize).div_ceil(2);
+        for i in first..last {
+            if to_row[i] == from_row[i] {
+                continue;
+            }
+            for (x, shift) in [(2 * i as u32, 0), (2 * i as u32 + 1, 4)] {
+                let t = (to_row[i] >> shift) & 0x0F;
+                let f = (from_row[i] >> shift) & 0x0F;
+                if t != f && area.contains(x, y) {
+                    visit(x, y, t, f);
+                }
+            }
+        }
+    }
+}
+
+/// Mark every row of the display in which the buffers differ, like the
+/// `dirty_lines` array filled by epdiy.
+/// ```
+/// use epd_gfx::Gray4;
+/// let from = vec![0xFF; 960 * 540 / 2];
+/// let mut to = from.clone();
+/// epd_gfx::fill_rect(&mut to, 3, 0, 2, 1, Gray4::BLACK);
+/// let lines = epd_gfx::diff::dirty_lines(&to, &from);
+/// assert_eq!(lines.iter().filter(|&&l| l).count(), 2);
+/// assert!(lines[3] && lines[4]);
+/// ```
+pub fn dirty_lines(to: &[u8], from: &[u8]) -> Vec<bool> {
+    check_sizes(to, from);
+    to.chunks_exact(ROW_BYTES)
+        .zip(from.chunks_exact(ROW_BYTES))
+        .map(|(t, f)| t != f)
+        .collect()
+}
+
+/// The smallest rectangle containing all changed pixels within `area`.
+/// ```
+/// use epd_gfx::rect::Rect;
+/// use epd_gfx::Gray4;
+/// let from = vec![0xFF; 960 * 540 / 2];
+/// let mut to = from.clone();
+/// epd_gfx::fill_rect(&mut to, 10, 20, 30, 40, Gray4::BLACK);
+/// let bounds = epd_gfx::diff::changed_bounds(&to, &from, Rect::landscape());
+/// assert_eq!(bounds, Some(Rect::new(900, 10, 40, 30)));
+/// assert_eq!(epd_gfx::diff::changed_bounds(&to, &to, Rect::landscape()), None);
+/// ```
+pub fn changed_bounds(to: &[u8], from: &[u8], area: Rect) -> Option<Rect> {
+    let mut bounds: Option<Rect> = None;
+    for_each_change(to, from, area, |x, y, _, _| {
+        let pixel = Rect::new(x, y, 1, 1);
+        bounds = Some(bounds.map_or(pixel, |b| b.union(&pixel)));
+    });
+    bounds
+}
+
+/// Disjoint rectangles covering all changed pixels within `area`.
+///
+/// Changes far apart end up in separate rectangles, so that they can be
+/// updated without refreshing everything in between.
+/// ```
+/// use epd_gfx::rect::Rect;
+/// use epd_gfx::Gray4;
+/// let from = vec![0xFF; 960 * 540 / 2];
+/// let mut to = from.clone();
+/// epd_gfx::fill_rect(&mut to, 0, 0, 10, 10, Gray4::BLACK);
+/// epd_gfx::fill_rect(&mut to, 500, 900, 10, 10, Gray4::BLACK);
+/// let mut rects = epd_gfx::diff::changed_rects(&to, &from, Rect::landscape());
+/// rects.sort_by_key(|r| r.x);
+/// assert_eq!(rects, vec![Rect::new(50, 500, 10, 10), Rect::new(950, 0, 10, 10)]);
+/// ```
+pub fn changed_rects(to: &[u8], from: &[u8], area: Rect) -> Vec<Rect> {
+    let mut done = Vec::new();
+    let mut open: Vec<Rect> = Vec::new();
+    let mut row = 0;
+    let mut spans: Vec<Rect> = Vec::new();
+
+    // Collect the changed spans of each row and grow the rectangles of the
+    // previous row which they touch.
+    let mut finish_row = |y: u32, spans: &mut Vec<Rect>, open: &mut Vec<Rect>| {
+        let mut next = Vec::new();
+        for span in spans.drain(..) {
+            let mut rect = span;
+            open.retain(|r| {
+                let touches = r.bottom() == y && r.is_near(&span, 0);
+                if touches {
+                    rect = rect.union(r);
+                }
+                !touches
+            });
+            next.push(rect);
+        }
+        // Rectangles which did not continue in this row are complete
+        done.append(open);
+        *open = next;
+    };
+
+    for_each_change(to, from, area, |x, y, _, _| {
+        if y != row {
+            finish_row(row, &mut spans, &mut open);
+            row = y;
+        }
+        match spans.last_mut() {
+            Some(span) if x <= span.right() + SPAN_GAP => span.width = x + 1 - span.x,
+            _ => spans.push(Rect::new(x, y, 1, 1)),
+        }
+    });
+    finish_row(row, &mut spans, &mut open);
+    finish_row(row + 1, &mut spans, &mut open);
+
+    coalesce(&mut done);
+    done
+}
+
+/// Gather statistics about the changes within `area`.
+/// ```
+/// use epd_gfx::rect::Rect;
+/// use epd_gfx::Gray4;
+/// let from = vec![0xFF; 960 * 540 / 2];
+/// let mut to = from.clone();
+/// epd_gfx::fill_rect(&mut to, 0, 0, 4, 1, Gray4::BLACK);
+/// let stats = epd_gfx::diff::diff_stats(&to, &from, Rect::landscape());
+/// assert_eq!((stats.changed_pixels, stats.changed_lines), (4, 4));
+/// assert!(stats.previously_white && stats.only_black_white);
+///
+/// epd_gfx::draw_pixel(&mut to, 0, 0, Gray4::GRAY);
+/// let stats = epd_gfx::diff::diff_stats(&to, &from, Rect::landscape());
+/// assert!(!stats.to_black_white);
+/// ```
+pub fn diff_stats(to: &[u8], from: &[u8], area: Rect) -> DiffStats {
+    check_sizes(to, from);
+    let Some(area) = area.intersection(&Rect::landscape()) else {
+        return DiffStats::default();
+    };
+
+    // The previous colors of all pixels are needed, not only changed ones
+    let mut from_or = 0x00;
+    let mut from_and = 0x0F;
+    for y in area.y..area.bottom() {
+        for x in area.x..area.right() {
+            let byte = from[y as usize * ROW_BYTES + x as usize / 2];
+            let color = if x % 2 == 0 { byte & 0x0F } else { byte >> 4 };
+            from_or |= color;
+            from_and &= color;
+        }
+    }
+
+    let mut stats = DiffStats {
+        previously_white: from_and == 0x0F,
+        previously_black: from_or == 0x00,
+        to_black_white: true,
+        only_black_white: true,
+        ..DiffStats::default()
+    };
+    let mut last_line = None;
+    for_each_change(to, from, area, |x, y, t, f| {
+        let is_bw = |c: u8| c == 0x0 || c == 0xF;
+        stats.changed_pixels += 1;
+        if last_line != Some(y) {
+            stats.changed_lines += 1;
+            last_line = Some(y);
+        }
+        let pixel = Rect::new(x, y, 1, 1);
+        stats.bounds = Some(stats.bounds.map_or(pixel, |b| b.union(&pixel)));
+        stats.to_black_white &= is_bw(t);
+        stats.only_black_white &= is_bw(t) && is_bw(f);
+    });
+    stats
+}
@@ -0,0 +1,168 @@
+//! Tracking which parts of a framebuffer changed, for partial refreshes.
+
+use crate::rect::{coalesce, merge_waste, Rect};
+use crate::{Framebuffer, Gray4};
+
+/// Rectangles closer than this many pixels are merged while drawing, so that
+/// e.g. the pixels of a glyph end up in a single rectangle.
+const MERGE_GAP: u32 = 8;
+
+/// Above this many rectangles, the two which waste the least area when
+/// merged are combined.
+const MAX_RECTS: usize = 16;
+
+/// A framebuffer wrapper which records the areas touched by drawing
+/// operations.
+///
+/// The dirty areas are kept in landscape (panel) coordinates, so they can be
+/// passed to `epd_hl_update_area` as they are.
+/// ```
+/// use epd_gfx::dirty::DirtyTracker;
+/// use epd_gfx::rect::Rect;
+/// use epd_gfx::Gray4;
+///
+/// let mut fb = DirtyTracker::new(vec![0xFF; 960 * 540 / 2]);
+/// epd_gfx::fill_rect(&mut fb, 10, 20, 30, 40, Gray4::BLACK);
+/// epd_gfx::draw_pixel(&mut fb, 11, 21, Gray4::GRAY);
+/// assert_eq!(fb.dirty_rects(), vec![Rect::new(900, 10, 40, 30)]);
+///
+/// fb.reset_dirty();
+/// assert!(!fb.is_dirty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct DirtyTracker<F> {
+    fb: F,
+    rects: Vec<Rect>,
+}
+
+impl<F: Framebuffer> DirtyTracker<F> {
+    /// Start tracking changes to `fb`, which is considered clean.
+    pub fn new(fb: F) -> Self {
+        Self {
+            fb,
+            rects: Vec::new(),
+        }
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.fb
+    }
+
+    /// Access the framebuffer directly. Changes made through it are not
+    /// tracked, use [`DirtyTracker::mark_dirty`] to record them.
+    pub fn inner_mut(&mut self) -> &mut F {
+        &mut self.fb
+    }
+
+    pub fn into_inner(self) -> F {
+        self.fb
+    }
+
+    /// Record a change to an area given in portrait coordinates.
+    pub fn mark_dirty(&mut self, area: Rect) {
+        if let Some(area) = area.to_landscape() {
+            self.add(area);
+        }
+    }
+
+    /// Record a change to the whole display.
+    pub fn mark_all_dirty(&mut self) {
+        self.rects.clear();
+        self.rects.push(Rect::landscape());
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.rects.is_empty()
+    }
+
+    /// Forget all changes, e.g. after the screen has been updated.
+    pub fn reset_dirty(&mut self) {
+        self.rects.clear();
+    }
+
+    /// The smallest rectangle containing all changes, in landscape
+    /// coordinates.
+    pub fn dirty_bounds(&self) -> Option<Rect> {
+        self.rects.iter().copied().reduce(|a, b| a.union(&b))
+    }
+
+    /// Disjoint rectangles covering all changes, in landscape coordinates.
+    ///
+    /// Rectangles are merged whenever refreshing their union would not
+    /// update more pixels than refreshing both separately.
+    /// ```
+    /// use epd_gfx::dirty::DirtyTracker;
+    /// use epd_gfx::rect::Rect;
+    /// use epd_gfx::Gray4;
+    ///
+    /// let mut fb = DirtyTracker::new(vec![0xFF; 960 * 540 / 2]);
+    /// epd_gfx::fill_rect(&mut fb, 0, 0, 100, 100, Gray4::BLACK);
+    /// epd_gfx::fill_rect(&mut fb, 50, 50, 100, 100, Gray4::BLACK);
+    /// epd_gfx::fill_rect(&mut fb, 400, 800, 10, 10, Gray4::BLACK);
+    /// assert_eq!(
+    ///     fb.dirty_rects(),
+    ///     vec![Rect::new(810, 0, 150, 150), Rect::new(150, 400, 10, 10)]
+    /// );
+    /// ```
+    pub fn dirty_rects(&self) -> Vec<Rect> {
+        let mut rects = self.rects.clone();
+        coalesce(&mut rects);
+        rects
+    }
+
+    fn add(&mut self, area: Rect) {
+        if let Some(near) = self
+            .rects
+            .iter_mut()
+            .rev()
+            .find(|r| r.is_near(&area, MERGE_GAP))
+        {
+            *near = near.union(&area);
+            return;
+        }
+        self.rects.push(area);
+        if self.rects.len() > MAX_RECTS {
+            self.merge_cheapest();
+        }
+    }
+
+    /// Merge the two rectangles whose union wastes the least area.
+    fn merge_cheapest(&mut self) {
+        let mut best = (u64::MAX, 0, 0);
+        for i in 0..self.rects.len() {
+            for j in i + 1..self.rects.len() {
+                let waste = merge_waste(&self.rects[i], &self.rects[j]);
+                if waste < best.0 {
+                    best = (waste, i, j);
+                }
+            }
+        }
+        let (_, i, j) = best;
+        self.rects[i] = self.rects[i].union(&self.rects[j]);
+        self.rects.swap_remove(j);
+    }
+}
+
+impl<F: Framebuffer> Framebuffer for DirtyTracker<F> {
+    fn draw_pixel(&mut self, x: u32, y: u32, color: Gray4) {
+        self.fb.draw_pixel(x, y, color);
+        self.mark_dirty(Rect::new(x, y, 1, 1));
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Option<Gray4> {
+        self.fb.get_pixel(x, y)
+    }
+
+    fn width(&self) -> u32 {
+        self.fb.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.fb.height()
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Gray4) {
+        self.fb.fill_rect(x, y, w, h, color);
+        self.mark_dirty(Rect::new(x, y, w, h));
+    }
+}
@@ -0,0 +1,317 @@
+//! Preprocessing for photos before they are quantized to the 16 gray levels of
+//! the display.
+//!
+//! All functions operate on 8 bit grayscale buffers (`0x00` black through
+//! `0xFF` white), stored row by row without padding.
+
+use crate::{draw_pixel, Framebuffer, Gray4};
+
+/// Pixel count for each of the 256 gray values.
+pub type Histogram = [u32; 256];
+
+/// Count how often each gray value occurs in `buf`.
+/// ```
+/// let h = epd_gfx::enhance::histogram(&[0, 0, 128, 255]);
+/// assert_eq!((h[0], h[128], h[255]), (2, 1, 1));
+/// ```
+pub fn histogram(buf: &[u8]) -> Histogram {
+    let mut hist = [0u32; 256];
+    buf.iter().for_each(|&v| hist[v as usize] += 1);
+    hist
+}
+
+/// Replace every value in `buf` by its entry in the lookup table.
+pub fn apply_lut(buf: &mut [u8], lut: &[u8; 256]) {
+    buf.iter_mut().for_each(|v| *v = lut[*v as usize]);
+}
+
+fn clamp_u8(v: f32) -> u8 {
+    v.round().clamp(0.0, 255.0) as u8
+}
+
+/// Shift brightness by `brightness` and scale contrast around median gray.
+///
+/// A `contrast` of `1.0` leaves contrast unchanged, values above increase it.
+/// ```
+/// let mut buf = [100, 128, 156];
+/// epd_gfx::enhance::brightness_contrast(&mut buf, 10, 2.0);
+/// assert_eq!(buf, [82, 138, 194]);
+/// ```
+pub fn brightness_contrast(buf: &mut [u8], brightness: i16, contrast: f32) {
+    let mut lut = [0u8; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        let v = (i as f32 - 128.0) * contrast + 128.0 + brightness as f32;
+        *entry = clamp_u8(v);
+    }
+    apply_lut(buf, &lut);
+}
+
+/// Apply gamma correction. Values of `gamma` below `1.0` brighten mid-tones,
+/// which usually helps on e-paper where dark grays tend to merge.
+/// ```
+/// let mut buf = [0, 64, 255];
+/// epd_gfx::enhance::gamma(&mut buf, 0.5);
+/// assert_eq!(buf, [0, 128, 255]);
+/// ```
+pub fn gamma(buf: &mut [u8], gamma: f32) {
+    let mut lut = [0u8; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        *entry = clamp_u8((i as f32 / 255.0).powf(gamma) * 255.0);
+    }
+    apply_lut(buf, &lut);
+}
+
+/// Stretch the gray values so that they span the full range.
+///
+/// `clip` is the fraction of pixels (e.g. `0.005`) which may be saturated at
+/// each end, so that a few outliers do not prevent the stretch.
+/// ```
+/// let mut buf = [64, 128, 192];
+/// epd_gfx::enhance::auto_levels(&mut buf, 0.0);
+/// assert_eq!(buf, [0, 128, 255]);
+/// ```
+pub fn auto_levels(buf: &mut [u8], clip: f32) {
+    if buf.is_empty() {
+        return;
+    }
+    let hist = histogram(buf);
+    let limit = (buf.len() as f32 * clip.clamp(0.0, 0.5)) as u32;
+
+    let mut low = 0;
+    let mut count = 0;
+    for (i, &n) in hist.iter().enumerate() {
+        count += n;
+        if count > limit {
+            low = i;
+            break;
+        }
+    }
+    let mut high = 255;
+    count = 0;
+    for (i, &n) in hist.iter().enumerate().rev() {
+        count += n;
+        if count > limit {
+            high = i;
+            break;
+        }
+    }
+    if high <= low {
+        return;
+    }
+
+    let scale = 255.0 / (high - low) as f32;
+    let mut lut = [0u8; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        *entry = clamp_u8((i as f32 - low as f32) * scale);
+    }
+    apply_lut(buf, &lut);
+}
+
+/// Build the equalization mapping for a histogram covering `total` pixels.
+fn equalization_lut(hist: &Histogram, total: u32) -> [u8; 256] {
+    let mut lut = [0u8; 256];
+    let cdf_min = hist.iter().copied().find(|&n| n > 0).unwrap_or(0);
+    let range = total.saturating_sub(cdf_min);
+    let mut cdf = 0;
+    for (i, &n) in hist.iter().enumerate() {
+        cdf += n;
+        lut[i] = if range == 0 {
+            i as u8
+        } else {
+            clamp_u8(cdf.saturating_sub(cdf_min) as f32 * 255.0 / range as f32)
+        };
+    }
+    lut
+}
+
+/// Spread the gray values evenly over the whole range (global histogram
+/// equalization).
+/// ```
+/// let mut buf = [10, 10, 20, 30];
+/// epd_gfx::enhance::equalize_histogram(&mut buf);
+/// assert_eq!(buf, [0, 0, 128, 255]);
+/// ```
+pub fn equalize_histogram(buf: &mut [u8]) {
+    let lut = equalization_lut(&histogram(buf), buf.len() as u32);
+    apply_lut(buf, &lut);
+}
+
+/// Contrast limited adaptive histogram equalization.
+///
+/// The image is split into `tiles_x` by `tiles_y` tiles which are equalized
+/// separately and blended bilinearly. `clip_limit` bounds each histogram bin to
+/// a multiple of the average bin count, with `2.0`–`4.0` being typical values;
+/// lower values amplify noise less. Tiles are at least one pixel large, so
+/// there may be fewer of them than requested.
+/// ```
+/// // 7 pixels do not split into 5 tiles of equal size
+/// let mut buf: Vec<u8> = (0..49).map(|i| (i % 7) as u8 * 40).collect();
+/// epd_gfx::enhance::clahe(&mut buf, 7, 7, 5, 5, 2.0);
+/// assert_eq!(buf[0], 0);
+/// assert!(buf.contains(&255));
+/// ```
+pub fn clahe(buf: &mut [u8], width: u32, height: u32, tiles_x: u32, tiles_y: u32, clip_limit: f32) {
+    let (w, h) = (width as usize, height as usize);
+    assert_eq!(buf.len(), w * h, "buffer does not match dimensions");
+    if w == 0 || h == 0 {
+        return;
+    }
+    let tiles_x = (tiles_x as usize).clamp(1, w);
+    let tiles_y = (tiles_y as usize).clamp(1, h);
+    let tile_w = w.div_ceil(tiles_x);
+    let tile_h = h.div_ceil(tiles_y);
+    // Rounding up the tile size can leave trailing tiles empty
+    let tiles_x = w.div_ceil(tile_w);
+    let tiles_y = h.div_ceil(tile_h);
+
+    // Calculate the mapping of each tile
+    let mut luts = vec![[0u8; 256]; tiles_x * tiles_y];
+    for ty in 0..tiles_y {
+        for tx in 0..tiles_x {
+            let (x0, y0) = (tx * tile_w, ty * tile_h);
+            let (x1, y1) = ((x0 + tile_w).min(w), (y0 + tile_h).min(h));
+            let mut hist = [0u32; 256];
+            for y in y0..y1 {
+                buf[y * w + x0..y * w + x1]
+                    .iter()
+                    .for_each(|&v| hist[v as usize] += 1);
+            }
+            let total = ((x1 - x0) * (y1 - y0)) as u32;
+            clip_histogram(&mut hist, total, clip_limit);
+            luts[ty * tiles_x + tx] = equalization_lut(&hist, total);
+        }
+    }
+
+    // Interpolate between the mappings of the four nearest tile centers
+    for y in 0..h {
+        let fy = (y as f32 - tile_h as f32 / 2.0) / tile_h as f32;
+        let ty0 = fy.floor().clamp(0.0, (tiles_y - 1) as f32) as usize;
+        let ty1 = (ty0 + 1).min(tiles_y - 1);
+        let wy = (fy - ty0 as f32).clamp(0.0, 1.0);
+        for x in 0..w {
+            let fx = (x as f32 - tile_w as f32 / 2.0) / tile_w as f32;
+            let tx0 = fx.floor().clamp(0.0, (tiles_x - 1) as f32) as usize;
+            let tx1 = (tx0 + 1).min(tiles_x - 1);
+            let wx = (fx - tx0 as f32).clamp(0.0, 1.0);
+
+            let v = buf[y * w + x] as usize;
+            let top = luts[ty0 * tiles_x + tx0][v] as f32 * (1.0 - wx)
+                + luts[ty0 * tiles_x + tx1][v] as f32 * wx;
+            let bottom = luts[ty1 * tiles_x + tx0][v] as f32 * (1.0 - wx)
+                + luts[ty1 * tiles_x + tx1][v] as f32 * wx;
+            buf[y * w + x] = clamp_u8(top * (1.0 - wy) + bottom * wy);
+        }
+    }
+}
+
+/// Clip all histogram bins at `clip_limit` times the average and redistribute
+/// the excess evenly.
+fn clip_histogram(hist: &mut Histogram, total: u32, clip_limit: f32) {
+    let limit = ((clip_limit * total as f32 / 256.0) as u32).max(1);
+    let mut excess = 0;
+    for n in hist.iter_mut() {
+        if *n > limit {
+            excess += *n - limit;
+            *n = limit;
+        }
+    }
+    let bonus = excess / 256;
+    let remainder = (excess % 256) as usize;
+    for (i, n) in hist.iter_mut().enumerate() {
+        *n += bonus + u32::from(i < remainder);
+    }
+}
+
+/// Blur `buf` with a gaussian kernel of standard deviation `radius`.
+fn gaussian_blur(buf: &[u8], width: usize, height: usize, radius: f32) -> Vec<u8> {
+    let reach = (radius * 3.0).ceil().max(1.0) as isize;
+    let kernel: Vec<f32> = (-reach..=reach)
+        .map(|i| (-(i * i) as f32 / (2.0 * radius * radius)).exp())
+        .collect();
+    let norm: f32 = kernel.iter().sum();
+
+    let blur = |src: &[u8], dst: &mut [u8], step: usize, len: usize, lines: usize, pitch: usize| {
+        for line in 0..lines {
+            for i in 0..len {
+                let mut acc = 0.0;
+                for (k, weight) in kernel.iter().enumerate() {
+                    let j = (i as isize + k as isize - reach).clamp(0, len as isize - 1) as usize;
+                    acc += src[line * pitch + j * step] as f32 * weight;
+                }
+                dst[line * pitch + i * step] = clamp_u8(acc / norm);
+            }
+        }
+    };
+
+    let mut horizontal = vec![0u8; buf.len()];
+    blur(buf, &mut horizontal, 1, width, height, width);
+    let mut result = vec![0u8; buf.len()];
+    blur(&horizontal, &mut result, width, height, width, 1);
+    result
+}
+
+/// Sharpen edges with an unsharp mask.
+///
+/// `radius` is the standard deviation of the blur in pixels, `amount` the
+/// strength (`1.0` adds the full difference) and differences of at most
+/// `threshold` are left alone to avoid amplifying noise.
+pub fn unsharp_mask(
+    buf: &mut [u8],
+    width: u32,
+    height: u32,
+    radius: f32,
+    amount: f32,
+    threshold: u8,
+) {
+    let (w, h) = (width as usize, height as usize);
+    assert_eq!(buf.len(), w * h, "buffer does not match dimensions");
+    if w == 0 || h == 0 || radius <= 0.0 {
+        return;
+    }
+    let blurred = gaussian_blur(buf, w, h, radius);
+    for (v, b) in buf.iter_mut().zip(blurred) {
+        let diff = *v as i16 - b as i16;
+        if diff.unsigned_abs() > threshold as u16 {
+            *v = clamp_u8(*v as f32 + diff as f32 * amount);
+        }
+    }
+}
+
+/// A set of preprocessing steps which give good results for typical photos
+/// on the 16 gray levels of the display.
+pub fn enhance_for_epaper(buf: &mut [u8], width: u32, height: u32) {
+    auto_levels(buf, 0.005);
+    clahe(buf, width, height, 8, 8, 2.0);
+    gamma(buf, 0.8);
+    unsharp_mask(buf, width, height, 1.0, 0.6, 2);
+}
+
+/// Quantize an 8 bit gray value to the 4 bits used by the framebuffer.
+/// ```
+/// use epd_gfx::Gray4;
+/// assert_eq!(epd_gfx::enhance::to_4bpp(0x00), Gray4::BLACK);
+/// assert_eq!(epd_gfx::enhance::to_4bpp(0x88), Gray4::GRAY);
+/// assert_eq!(epd_gfx::enhance::to_4bpp(0xFF), Gray4::WHITE);
+/// ```
+pub fn to_4bpp(value: u8) -> Gray4 {
+    Gray4::from_luma(value)
+}
+
+/// Quantize an 8 bit grayscale image and draw it with its top left corner at
+/// (x, y).
+pub fn draw_gray_image<F: Framebuffer + ?Sized>(
+    fb: &mut F,
+    x: u32,
+    y: u32,
+    width: u32,
+    image: &[u8],
+) {
+    if width == 0 {
+        return;
+    }
+    for (i, row) in image.chunks(width as usize).enumerate() {
+        for (j, &v) in row.iter().enumerate() {
+            draw_pixel(fb, x + j as u32, y + i as u32, to_4bpp(v));
+        }
+    }
+}
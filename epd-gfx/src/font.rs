@@ -1,11 +1,17 @@
-use crate::draw_pixel;
-use rusttype::{point, Font, Scale};
+use crate::{draw_pixel, Framebuffer, Gray4};
+use rusttype::point;
+pub use rusttype::{Font, Scale};
 
-pub fn draw_text(fb: &mut [u8], x: u32, y: u32, text: &str, size: u32) {
-    // Load the font
+/// The font built into this crate, Karla Medium.
+pub fn karla() -> Font<'static> {
     let font_data = include_bytes!("../fonts/Karla-Medium.ttf");
     // This only succeeds if collection consists of one font
-    let font = Font::try_from_bytes(font_data as &[u8]).expect("Error constructing Font");
+    Font::try_from_bytes(font_data as &[u8]).expect("Error constructing Font")
+}
+
+pub fn draw_text<F: Framebuffer + ?Sized>(fb: &mut F, x: u32, y: u32, text: &str, size: u32) {
+    // Load the font
+    let font = karla();
 
     // The font size to use
     let scale = Scale::uniform(size as f32);
@@ -29,9 +35,75 @@ pub fn draw_text(fb: &mut [u8], x: u32, y: u32, text: &str, size: u32) {
                     x_pos + x + bounding_box.min.x as u32,
                     y_pos + y + bounding_box.min.y as u32,
                     // Turn the coverage into a grayscale value
-                    ((1.0 - v) * 15.0) as u8,
+                    Gray4::new(((1.0 - v) * 15.0) as u8),
                 )
             });
         }
     }
 }
+
+/// Draw text in `color` with its top left corner at (x, y).
+///
+/// Unlike [`draw_text`], the background is kept: the coverage of each pixel
+/// blends `color` with the color already there. Pixels left of or above the
+/// display are skipped.
+/// ```
+/// use epd_gfx::font::Scale;
+/// use epd_gfx::Gray4;
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// let font = epd_gfx::font::karla();
+/// epd_gfx::font::fill_text(&mut fb, &font, 0.0, 0.0, "I", Scale::uniform(32.0), Gray4::BLACK);
+/// assert_eq!(epd_gfx::render::pixels(&fb, 0, 0, 40, 40).min(), Some(Gray4::BLACK));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 39, 39), Some(Gray4::WHITE));
+/// ```
+pub fn fill_text<F: Framebuffer + ?Sized>(
+    fb: &mut F,
+    font: &Font,
+    x: f32,
+    y: f32,
+    text: &str,
+    scale: Scale,
+    color: Gray4,
+) {
+    fill_text_with(fb, font, x, y, text, scale, |_, _| color);
+}
+
+/// Like [`fill_text`], with the color of each pixel given by `color`.
+pub(crate) fn fill_text_with<F: Framebuffer + ?Sized>(
+    fb: &mut F,
+    font: &Font,
+    x: f32,
+    y: f32,
+    text: &str,
+    scale: Scale,
+    color: impl Fn(u32, u32) -> Gray4,
+) {
+    let ascent = font.v_metrics(scale).ascent;
+    for glyph in font.layout(text, scale, point(x, y + ascent)) {
+        let Some(bounding_box) = glyph.pixel_bounding_box() else {
+            continue;
+        };
+        glyph.draw(|gx, gy, v| {
+            let px = bounding_box.min.x + gx as i32;
+            let py = bounding_box.min.y + gy as i32;
+            if px < 0 || py < 0 || v <= 0.0 {
+                return;
+            }
+            let (px, py) = (px as u32, py as u32);
+            if let Some(old) = fb.get_pixel(px, py) {
+                let (old, new) = (old.level() as f32, color(px, py).level() as f32);
+                let blended = old + (new - old) * v.min(1.0);
+                fb.draw_pixel(px, py, Gray4::from_level_f32(blended));
+            }
+        });
+    }
+}
+
+/// The horizontal advance of `text`, in pixels.
+pub fn text_width(font: &Font, text: &str, scale: Scale) -> f32 {
+    font.layout(text, scale, point(0.0, 0.0))
+        .last()
+        .map_or(0.0, |glyph| {
+            glyph.position().x + glyph.unpositioned().h_metrics().advance_width
+        })
+}
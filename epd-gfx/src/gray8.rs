@@ -0,0 +1,159 @@
+//! 8 bit-per-pixel framebuffers and epdiy's interlaced difference format.
+//!
+//! Like the other framebuffers, an 8bpp framebuffer is stored in landscape
+//! orientation, with one byte per pixel from `0x00` (black) to `0xFF` (white).
+//! The extra precision is useful to compose images before they are reduced to
+//! the 16 gray levels of the display.
+//!
+//! `MODE_PACKING_1PPB_DIFFERENCE` buffers have the same size and layout, but
+//! each byte holds the 4 bit target color of a pixel in the upper nibble and
+//! its previous color in the lower nibble. With such a buffer,
+//! `epd_draw_base` can be called directly instead of going through the
+//! front/back buffers of the high-level API.
+
+use crate::color::Gray8;
+use crate::{landscape_rect, split_byte, to_landscape, Framebuffer, Gray4, EPD_HEIGHT, EPD_WIDTH};
+
+/// Size of an 8 bit-per-pixel framebuffer or difference image in bytes.
+pub const GRAY8_FB_SIZE: usize = (EPD_WIDTH * EPD_HEIGHT) as usize;
+
+/// An 8 bit-per-pixel framebuffer.
+///
+/// Colors drawn through [`Framebuffer`] are expanded to 8 bits, so that
+/// [`Gray4::WHITE`] becomes [`Gray8::WHITE`].
+/// ```
+/// use epd_gfx::color::Gray8;
+/// use epd_gfx::gray8::Gray8Buffer;
+/// use epd_gfx::Gray4;
+/// let mut fb = Gray8Buffer::new();
+/// epd_gfx::draw_pixel(&mut fb, 0, 0, Gray4::GRAY);
+/// fb.set_gray8(1, 0, Gray8::new(0x42));
+/// assert_eq!(fb.gray8(0, 0), Some(Gray8::new(0x88)));
+/// assert_eq!(fb.gray8(1, 0), Some(Gray8::new(0x42)));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 1, 0), Some(Gray4::DARK_GRAY));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Gray8Buffer<B = Vec<u8>> {
+    buf: B,
+}
+
+impl Gray8Buffer<Vec<u8>> {
+    /// Allocate a new, white framebuffer.
+    pub fn new() -> Self {
+        Self {
+            buf: vec![0xFF; GRAY8_FB_SIZE],
+        }
+    }
+
+    /// Convert a 4bpp framebuffer.
+    pub fn from_4bpp(fb: &[u8]) -> Self {
+        assert_eq!(fb.len() * 2, GRAY8_FB_SIZE, "invalid framebuffer size");
+        let mut buf = Vec::with_capacity(GRAY8_FB_SIZE);
+        for &byte in fb {
+            let (left, right) = split_byte(byte);
+            buf.push(right * 0x11);
+            buf.push(left * 0x11);
+        }
+        Self { buf }
+    }
+}
+
+impl Default for Gray8Buffer<Vec<u8>> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Gray8Buffer<B> {
+    /// Use an existing buffer of [`GRAY8_FB_SIZE`] bytes.
+    pub fn from_buffer(buf: B) -> Self {
+        assert_eq!(buf.as_ref().len(), GRAY8_FB_SIZE, "invalid buffer size");
+        Self { buf }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.buf.as_ref()
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        self.buf.as_mut()
+    }
+
+    pub fn into_inner(self) -> B {
+        self.buf
+    }
+
+    /// The 8 bit gray value at (x, y), if it lies on the display.
+    pub fn gray8(&self, x: u32, y: u32) -> Option<Gray8> {
+        let (x, y) = to_landscape(x, y)?;
+        Some(Gray8::new(self.as_bytes()[(y * EPD_WIDTH + x) as usize]))
+    }
+
+    /// Set a pixel to an 8 bit gray value.
+    pub fn set_gray8(&mut self, x: u32, y: u32, value: Gray8) {
+        if let Some((x, y)) = to_landscape(x, y) {
+            self.buf.as_mut()[(y * EPD_WIDTH + x) as usize] = value.luma();
+        }
+    }
+
+    /// Reduce to a 4bpp framebuffer, keeping the upper 4 bits of each pixel.
+    pub fn to_4bpp(&self, fb: &mut [u8]) {
+        assert_eq!(fb.len() * 2, GRAY8_FB_SIZE, "invalid framebuffer size");
+        for (byte, pair) in fb.iter_mut().zip(self.as_bytes().chunks_exact(2)) {
+            *byte = (pair[1] & 0xF0) | (pair[0] >> 4);
+        }
+    }
+
+    /// Build a `MODE_PACKING_1PPB_DIFFERENCE` image for going from `from`
+    /// to this image. Both images are reduced to their upper 4 bits.
+    pub fn difference_from<C: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        from: &Gray8Buffer<C>,
+        interlaced: &mut [u8],
+    ) {
+        assert_eq!(interlaced.len(), GRAY8_FB_SIZE, "invalid buffer size");
+        let pixels = self.as_bytes().iter().zip(from.as_bytes());
+        for (out, (&to, &from)) in interlaced.iter_mut().zip(pixels) {
+            *out = (to & 0xF0) | (from >> 4);
+        }
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Framebuffer for Gray8Buffer<B> {
+    fn draw_pixel(&mut self, x: u32, y: u32, color: Gray4) {
+        self.set_gray8(x, y, color.into());
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Option<Gray4> {
+        self.gray8(x, y).map(Gray4::from)
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Gray4) {
+        if let Some((x0, x1, y0, y1)) = landscape_rect(x, y, w, h) {
+            let rows = self.buf.as_mut().chunks_exact_mut(EPD_WIDTH as usize);
+            for row in rows.take(y1 as usize).skip(y0 as usize) {
+                row[x0 as usize..x1 as usize].fill(color.to_luma());
+            }
+        }
+    }
+}
+
+/// Build a `MODE_PACKING_1PPB_DIFFERENCE` image from two 4bpp framebuffers,
+/// like epdiy's `epd_difference_image` does for the whole screen.
+/// Each output byte holds the `to` color in the upper and the `from` color in
+/// the lower nibble.
+/// ```
+/// let mut interlaced = [0u8; 4];
+/// epd_gfx::gray8::difference_image(&[0x0F, 0x88], &[0xFF, 0x80], &mut interlaced);
+/// assert_eq!(interlaced, [0xFF, 0x0F, 0x80, 0x88]);
+/// ```
+pub fn difference_image(to: &[u8], from: &[u8], interlaced: &mut [u8]) {
+    assert_eq!(to.len(), from.len(), "framebuffer sizes do not match");
+    assert_eq!(to.len() * 2, interlaced.len(), "invalid buffer size");
+    for ((pair, &t), &f) in interlaced.chunks_exact_mut(2).zip(to).zip(from) {
+        let (t_left, t_right) = split_byte(t);
+        let (f_left, f_right) = split_byte(f);
+        pair[0] = (t_right << 4) | f_right;
+        pair[1] = (t_left << 4) | f_left;
+    }
+}
@@ -0,0 +1,464 @@
+//! A compact native image format which can be blitted into the framebuffer
+//! without a full decoder.
+//!
+//! An image starts with a 12 byte header, followed by the pixel data:
+//!
+//! | Offset | Size | Content                                                  |
+//! |--------|------|----------------------------------------------------------|
+//! | 0      | 4    | Magic bytes `EPDI`                                       |
+//! | 4      | 1    | Format version, currently `1`                            |
+//! | 5      | 1    | Bits per pixel: 1, 2, 4 or 8                             |
+//! | 6      | 1    | Flags: bit 0 marks a transparent color, bits 4-5 the [`Compression`] |
+//! | 7      | 1    | Transparent color, in the same depth as the pixels       |
+//! | 8      | 2    | Width in pixels, little endian                           |
+//! | 10     | 2    | Height in pixels, little endian                          |
+//!
+//! Pixels are packed row by row, the leftmost pixel in the least significant
+//! bits of a byte, just like the 4bpp framebuffer. Rows are padded to whole
+//! bytes. The packed data may then be compressed as a whole.
+//!
+//! RLE decoding happens on the fly and needs no memory besides a few bytes of
+//! state. zlib compression is only available with the `zlib` feature and
+//! inflates the pixel data into a temporary buffer.
+
+use crate::{draw_pixel, Framebuffer, Gray4};
+use std::fmt;
+
+/// Magic bytes at the start of every image.
+pub const MAGIC: [u8; 4] = *b"EPDI";
+/// The format version written by [`encode`].
+pub const VERSION: u8 = 1;
+/// Size of the image header in bytes.
+pub const HEADER_SIZE: usize = 12;
+
+const FLAG_TRANSPARENT: u8 = 0x01;
+const COMPRESSION_SHIFT: u8 = 4;
+const COMPRESSION_MASK: u8 = 0x30;
+
+/// Compression of the pixel data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// The pixel data is stored as is.
+    None = 0,
+    /// PackBits style run length encoding: a control byte `c` below `0x80`
+    /// is followed by `c + 1` literal bytes, otherwise the next byte is
+    /// repeated `c - 0x80 + 1` times.
+    Rle = 1,
+    /// zlib (deflate) compression.
+    Zlib = 2,
+}
+
+impl Compression {
+    fn from_flags(flags: u8) -> Result<Self, ImageError> {
+        match (flags & COMPRESSION_MASK) >> COMPRESSION_SHIFT {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Rle),
+            #[cfg(feature = "zlib")]
+            2 => Ok(Compression::Zlib),
+            other => Err(ImageError::UnsupportedCompression(other)),
+        }
+    }
+}
+
+/// Reasons why image data can not be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageError {
+    /// The data is shorter than the header.
+    TooShort,
+    /// The data does not start with [`MAGIC`].
+    BadMagic,
+    /// The image was written by an unknown format version.
+    UnsupportedVersion(u8),
+    /// Only 1, 2, 4 and 8 bits per pixel are supported.
+    UnsupportedDepth(u8),
+    /// The compression method is unknown or was not enabled at compile time.
+    UnsupportedCompression(u8),
+    /// The pixel data ended before all pixels were decoded.
+    Truncated,
+    /// The compressed pixel data is corrupt.
+    Corrupt,
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::TooShort => write!(f, "image data is shorter than the header"),
+            ImageError::BadMagic => write!(f, "image data does not start with EPDI"),
+            ImageError::UnsupportedVersion(v) => write!(f, "unsupported image version {v}"),
+            ImageError::UnsupportedDepth(d) => write!(f, "unsupported bit depth {d}"),
+            ImageError::UnsupportedCompression(c) => write!(f, "unsupported compression {c}"),
+            ImageError::Truncated => write!(f, "pixel data is truncated"),
+            ImageError::Corrupt => write!(f, "compressed pixel data is corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// A parsed image header together with a reference to its pixel data.
+#[derive(Debug, Clone, Copy)]
+pub struct Image<'a> {
+    width: u32,
+    height: u32,
+    bpp: u8,
+    transparent: Option<u8>,
+    compression: Compression,
+    data: &'a [u8],
+}
+
+impl<'a> Image<'a> {
+    /// Parse the header of an encoded image, e.g. one included with
+    /// `include_bytes!`.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, ImageError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(ImageError::TooShort);
+        }
+        if bytes[0..4] != MAGIC {
+            return Err(ImageError::BadMagic);
+        }
+        if bytes[4] != VERSION {
+            return Err(ImageError::UnsupportedVersion(bytes[4]));
+        }
+        let bpp = bytes[5];
+        if !matches!(bpp, 1 | 2 | 4 | 8) {
+            return Err(ImageError::UnsupportedDepth(bpp));
+        }
+        let flags = bytes[6];
+        let compression = Compression::from_flags(flags)?;
+        let transparent = (flags & FLAG_TRANSPARENT != 0).then_some(bytes[7]);
+        let width = u16::from_le_bytes([bytes[8], bytes[9]]) as u32;
+        let height = u16::from_le_bytes([bytes[10], bytes[11]]) as u32;
+
+        Ok(Self {
+            width,
+            height,
+            bpp,
+            transparent,
+            compression,
+            data: &bytes[HEADER_SIZE..],
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Bits per pixel of the stored data.
+    pub fn bpp(&self) -> u8 {
+        self.bpp
+    }
+
+    /// The color key in the stored bit depth, if the image has one.
+    pub fn transparent_color(&self) -> Option<u8> {
+        self.transparent
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Number of bytes in one packed row.
+    pub fn row_bytes(&self) -> usize {
+        (self.width as usize * self.bpp as usize).div_ceil(8)
+    }
+
+    /// Iterate over the raw pixel values, row by row.
+    pub fn pixels(&self) -> Result<Pixels<'a>, ImageError> {
+        let bytes = match self.compression {
+            Compression::None => ByteStream::Raw(self.data.iter()),
+            Compression::Rle => ByteStream::Rle(RleDecoder::new(self.data)),
+            Compression::Zlib => ByteStream::Inflated(inflate(self.data)?.into_iter()),
+        };
+        Ok(Pixels {
+            bytes,
+            bpp: self.bpp,
+            width: self.width,
+            remaining: self.width as usize * self.height as usize,
+            x: 0,
+            current: 0,
+            bits_left: 0,
+        })
+    }
+
+    /// Scale a raw pixel value to the 4 bits of the framebuffer.
+    /// ```
+    /// # let data = epd_gfx::image::encode(1, 1, 2, &[0], None, epd_gfx::image::Compression::None);
+    /// let image = epd_gfx::image::Image::from_bytes(&data).unwrap();
+    /// assert_eq!(image.to_4bpp(3), epd_gfx::Gray4::WHITE);
+    /// assert_eq!(image.to_4bpp(1), epd_gfx::Gray4::new(0x5));
+    /// ```
+    pub fn to_4bpp(&self, value: u8) -> Gray4 {
+        match self.bpp {
+            1 => Gray4::from_low_nibble(value * 0xF),
+            2 => Gray4::from_low_nibble(value * 5),
+            4 => Gray4::from_low_nibble(value),
+            _ => Gray4::from_high_nibble(value),
+        }
+    }
+}
+
+#[cfg(feature = "zlib")]
+fn inflate(data: &[u8]) -> Result<Vec<u8>, ImageError> {
+    miniz_oxide::inflate::decompress_to_vec_zlib(data).map_err(|_| ImageError::Corrupt)
+}
+
+#[cfg(not(feature = "zlib"))]
+fn inflate(_data: &[u8]) -> Result<Vec<u8>, ImageError> {
+    Err(ImageError::UnsupportedCompression(Compression::Zlib as u8))
+}
+
+/// Streaming decoder for [`Compression::Rle`].
+#[derive(Debug, Clone)]
+struct RleDecoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    literal: usize,
+    repeat: usize,
+    value: u8,
+}
+
+impl<'a> RleDecoder<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            literal: 0,
+            repeat: 0,
+            value: 0,
+        }
+    }
+}
+
+impl Iterator for RleDecoder<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.repeat > 0 {
+            self.repeat -= 1;
+            return Some(self.value);
+        }
+        if self.literal == 0 {
+            let control = *self.data.get(self.pos)?;
+            self.pos += 1;
+            if control < 0x80 {
+                self.literal = control as usize + 1;
+            } else {
+                self.value = *self.data.get(self.pos)?;
+                self.pos += 1;
+                self.repeat = (control - 0x80) as usize;
+                return Some(self.value);
+            }
+        }
+        let byte = *self.data.get(self.pos)?;
+        self.pos += 1;
+        self.literal -= 1;
+        Some(byte)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ByteStream<'a> {
+    Raw(std::slice::Iter<'a, u8>),
+    Rle(RleDecoder<'a>),
+    Inflated(std::vec::IntoIter<u8>),
+}
+
+impl Iterator for ByteStream<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        match self {
+            ByteStream::Raw(iter) => iter.next().copied(),
+            ByteStream::Rle(decoder) => decoder.next(),
+            ByteStream::Inflated(iter) => iter.next(),
+        }
+    }
+}
+
+/// Iterator over the raw pixel values of an [`Image`], row by row.
+///
+/// Yields `Err(ImageError::Truncated)` once if the pixel data ends early.
+#[derive(Debug, Clone)]
+pub struct Pixels<'a> {
+    bytes: ByteStream<'a>,
+    bpp: u8,
+    width: u32,
+    remaining: usize,
+    x: u32,
+    current: u8,
+    bits_left: u8,
+}
+
+impl Iterator for Pixels<'_> {
+    type Item = Result<u8, ImageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        if self.bits_left == 0 {
+            match self.bytes.next() {
+                Some(byte) => {
+                    self.current = byte;
+                    self.bits_left = 8;
+                }
+                None => {
+                    self.remaining = 0;
+                    return Some(Err(ImageError::Truncated));
+                }
+            }
+        }
+        let mask = ((1u16 << self.bpp) - 1) as u8;
+        let value = self.current & mask;
+        self.current = self.current.checked_shr(self.bpp as u32).unwrap_or(0);
+        self.bits_left -= self.bpp;
+        self.remaining -= 1;
+
+        // Rows start on a new byte
+        self.x += 1;
+        if self.x == self.width {
+            self.x = 0;
+            self.bits_left = 0;
+        }
+        Some(Ok(value))
+    }
+}
+
+/// Draw an image with its top left corner at (x, y).
+/// Pixels matching the transparent color are skipped.
+/// ```
+/// use epd_gfx::image::{encode, Compression, Image};
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// let data = encode(2, 1, 4, &[0x0, 0x8], None, Compression::Rle);
+/// epd_gfx::image::draw_image(&mut fb, 0, 0, &Image::from_bytes(&data).unwrap()).unwrap();
+/// assert_eq!(fb[959 / 2], 0x0F);
+/// ```
+pub fn draw_image<F: Framebuffer + ?Sized>(
+    fb: &mut F,
+    x: u32,
+    y: u32,
+    image: &Image,
+) -> Result<(), ImageError> {
+    let width = image.width().max(1);
+    for (i, value) in image.pixels()?.enumerate() {
+        let value = value?;
+        if Some(value) == image.transparent_color() {
+            continue;
+        }
+        let (ix, iy) = (i as u32 % width, i as u32 / width);
+        draw_pixel(fb, x + ix, y + iy, image.to_4bpp(value));
+    }
+    Ok(())
+}
+
+/// Encode raw pixel values (one per byte, in the range of `bpp`) into the
+/// native image format.
+///
+/// Panics if the dimensions do not fit into 16 bits, if `pixels` does not
+/// contain `width * height` values, if `bpp` is not 1, 2, 4 or 8 or if zlib
+/// compression is requested without the `zlib` feature.
+/// ```
+/// use epd_gfx::image::{encode, Compression, Image};
+/// let pixels = [0x0, 0xF, 0xF, 0xF, 0xF, 0x8];
+/// let data = encode(3, 2, 4, &pixels, Some(0x8), Compression::Rle);
+/// let image = Image::from_bytes(&data).unwrap();
+/// assert_eq!((image.width(), image.height()), (3, 2));
+/// assert_eq!(image.transparent_color(), Some(0x8));
+/// let decoded: Vec<u8> = image.pixels().unwrap().map(Result::unwrap).collect();
+/// assert_eq!(decoded, pixels);
+/// ```
+pub fn encode(
+    width: u32,
+    height: u32,
+    bpp: u8,
+    pixels: &[u8],
+    transparent: Option<u8>,
+    compression: Compression,
+) -> Vec<u8> {
+    assert!(matches!(bpp, 1 | 2 | 4 | 8), "unsupported bit depth {bpp}");
+    assert!(width <= u16::MAX as u32 && height <= u16::MAX as u32);
+    assert_eq!(pixels.len(), (width * height) as usize);
+
+    let mut packed = Vec::new();
+    if width > 0 {
+        for row in pixels.chunks(width as usize) {
+            let mut byte = 0u8;
+            let mut shift = 0;
+            for &value in row {
+                byte |= (value & ((1u16 << bpp) - 1) as u8) << shift;
+                shift += bpp;
+                if shift == 8 {
+                    packed.push(byte);
+                    byte = 0;
+                    shift = 0;
+                }
+            }
+            if shift > 0 {
+                packed.push(byte);
+            }
+        }
+    }
+
+    let mut flags = (compression as u8) << COMPRESSION_SHIFT;
+    if transparent.is_some() {
+        flags |= FLAG_TRANSPARENT;
+    }
+    let mut data = Vec::with_capacity(HEADER_SIZE + packed.len());
+    data.extend_from_slice(&MAGIC);
+    data.extend_from_slice(&[VERSION, bpp, flags, transparent.unwrap_or(0)]);
+    data.extend_from_slice(&(width as u16).to_le_bytes());
+    data.extend_from_slice(&(height as u16).to_le_bytes());
+    match compression {
+        Compression::None => data.extend_from_slice(&packed),
+        Compression::Rle => data.extend_from_slice(&rle_encode(&packed)),
+        Compression::Zlib => data.extend_from_slice(&deflate(&packed)),
+    }
+    data
+}
+
+#[cfg(feature = "zlib")]
+fn deflate(data: &[u8]) -> Vec<u8> {
+    miniz_oxide::deflate::compress_to_vec_zlib(data, 10)
+}
+
+#[cfg(not(feature = "zlib"))]
+fn deflate(_data: &[u8]) -> Vec<u8> {
+    panic!("zlib compression requires the `zlib` feature");
+}
+
+/// Run length encode bytes, see [`Compression::Rle`].
+fn rle_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut literal_start = 0;
+    let mut i = 0;
+    while i < data.len() {
+        let run = data[i..]
+            .iter()
+            .take(128)
+            .take_while(|&&b| b == data[i])
+            .count();
+        // Runs of two are only worth it if they don't interrupt a literal
+        if run >= 3 || (run == 2 && literal_start == i) {
+            flush_literal(&mut out, &data[literal_start..i]);
+            out.push(0x80 + (run - 1) as u8);
+            out.push(data[i]);
+            i += run;
+            literal_start = i;
+        } else {
+            i += 1;
+        }
+    }
+    flush_literal(&mut out, &data[literal_start..]);
+    out
+}
+
+fn flush_literal(out: &mut Vec<u8>, literal: &[u8]) {
+    for chunk in literal.chunks(128) {
+        out.push((chunk.len() - 1) as u8);
+        out.extend_from_slice(chunk);
+    }
+}
@@ -0,0 +1,382 @@
+//! Offscreen buffers and layers composited into a framebuffer.
+//!
+//! Parts of the UI which change rarely, like a static background, or which
+//! come and go, like popups, can be drawn once into an [`Offscreen`] buffer
+//! and composited into the framebuffer whenever needed.
+
+use crate::rect::Rect;
+use crate::{fill_nibbles, Framebuffer, Gray4};
+
+/// A 4 bit-per-pixel buffer of any size.
+///
+/// Unlike the display framebuffer it is not rotated: rows are stored top to
+/// bottom with two pixels per byte, the left one in the lower nibble.
+/// ```
+/// use epd_gfx::layer::Offscreen;
+/// use epd_gfx::Gray4;
+/// let mut buffer = Offscreen::new(3, 2);
+/// epd_gfx::draw_pixel(&mut buffer, 2, 1, Gray4::DARK_GRAY);
+/// assert_eq!(buffer.as_bytes(), &[0xFF, 0xFF, 0xFF, 0xF4]);
+/// assert_eq!(epd_gfx::get_pixel(&buffer, 3, 1), None);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offscreen {
+    width: u32,
+    height: u32,
+    buf: Vec<u8>,
+}
+
+impl Offscreen {
+    /// Allocate a white buffer.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::filled(width, height, Gray4::WHITE)
+    }
+
+    /// Allocate a buffer filled with `color`.
+    pub fn filled(width: u32, height: u32, color: Gray4) -> Self {
+        let row_bytes = width.div_ceil(2) as usize;
+        Self {
+            width,
+            height,
+            buf: vec![color.packed(); row_bytes * height as usize],
+        }
+    }
+
+    /// Copy an area of a framebuffer, e.g. to restore it after closing a
+    /// popup. Pixels outside of `fb` become white.
+    pub fn copy_from<F: Framebuffer + ?Sized>(fb: &F, area: Rect) -> Self {
+        let mut buffer = Self::new(area.width, area.height);
+        for y in 0..area.height {
+            for x in 0..area.width {
+                let color = area
+                    .x
+                    .checked_add(x)
+                    .zip(area.y.checked_add(y))
+                    .and_then(|(fx, fy)| fb.get_pixel(fx, fy));
+                if let Some(color) = color {
+                    buffer.draw_pixel(x, y, color);
+                }
+            }
+        }
+        buffer
+    }
+
+    /// Fill the whole buffer with `color`, like [`crate::set_all`].
+    pub fn clear(&mut self, color: Gray4) {
+        self.buf.fill(color.packed());
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+
+    fn row_bytes(&self) -> usize {
+        self.width.div_ceil(2) as usize
+    }
+}
+
+impl Framebuffer for Offscreen {
+    fn draw_pixel(&mut self, x: u32, y: u32, color: Gray4) {
+        if x < self.width && y < self.height {
+            let index = y as usize * self.row_bytes() + x as usize / 2;
+            let byte = &mut self.buf[index];
+            *byte = if x.is_multiple_of(2) {
+                (*byte & 0xF0) | color.level()
+            } else {
+                (*byte & 0x0F) | (color.level() << 4)
+            };
+        }
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Option<Gray4> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let byte = self.buf[y as usize * self.row_bytes() + x as usize / 2];
+        Some(if x.is_multiple_of(2) {
+            Gray4::from_low_nibble(byte)
+        } else {
+            Gray4::from_high_nibble(byte)
+        })
+    }
+
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Gray4) {
+        let Some(area) =
+            Rect::new(x, y, w, h).intersection(&Rect::new(0, 0, self.width, self.height))
+        else {
+            return;
+        };
+        let row_bytes = self.row_bytes();
+        for row in self
+            .buf
+            .chunks_exact_mut(row_bytes)
+            .take(area.bottom() as usize)
+            .skip(area.y as usize)
+        {
+            fill_nibbles(row, area.x as usize, area.right() as usize, color);
+        }
+    }
+}
+
+/// How the pixels of a layer are combined with those below.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// The layer covers what is below.
+    #[default]
+    Replace,
+    /// Multiply both gray levels, so that white is transparent and black
+    /// stays black.
+    Multiply,
+    /// Keep the darker of both pixels.
+    Darken,
+    /// Keep the lighter of both pixels.
+    Lighten,
+    /// Combine the gray levels bit by bit with XOR, so that drawing the same
+    /// layer twice restores what was below.
+    Xor,
+    /// Invert what is below where the layer is black, and keep it where the
+    /// layer is white.
+    Invert,
+}
+
+impl BlendMode {
+    /// Combine a layer pixel `src` with the pixel `dst` below it.
+    /// ```
+    /// use epd_gfx::layer::BlendMode;
+    /// use epd_gfx::Gray4;
+    /// let dark = Gray4::new(0x3);
+    /// assert_eq!(BlendMode::Multiply.blend(Gray4::GRAY, Gray4::WHITE), Gray4::GRAY);
+    /// assert_eq!(BlendMode::Multiply.blend(Gray4::GRAY, Gray4::GRAY), Gray4::DARK_GRAY);
+    /// assert_eq!(BlendMode::Lighten.blend(Gray4::GRAY, dark), Gray4::GRAY);
+    /// assert_eq!(BlendMode::Invert.blend(Gray4::BLACK, dark), Gray4::LIGHT_GRAY);
+    /// assert_eq!(BlendMode::Invert.blend(Gray4::WHITE, dark), dark);
+    /// ```
+    pub fn blend(&self, src: Gray4, dst: Gray4) -> Gray4 {
+        match self {
+            BlendMode::Replace => src,
+            BlendMode::Multiply => {
+                let product = src.level() as u16 * dst.level() as u16;
+                Gray4::new(((product + 7) / 15) as u8)
+            }
+            BlendMode::Darken => src.min(dst),
+            BlendMode::Lighten => src.max(dst),
+            BlendMode::Xor => Gray4::new(src.level() ^ dst.level()),
+            BlendMode::Invert => mix(dst.invert(), dst, src.level() as f32 / 15.0),
+        }
+    }
+}
+
+/// Go from `a` to `b` by `t` (from 0 to 1).
+fn mix(a: Gray4, b: Gray4, t: f32) -> Gray4 {
+    let (a, b) = (a.level() as f32, b.level() as f32);
+    Gray4::from_level_f32(a + (b - a) * t)
+}
+
+/// Composite `src` at (x, y) into `fb`.
+///
+/// With an `opacity` below 1, the result is mixed with the original pixel.
+/// Pixels of `src` with the `transparent` color are skipped.
+/// ```
+/// use epd_gfx::layer::{blit, BlendMode, Offscreen};
+/// use epd_gfx::Gray4;
+/// let mut fb = vec![0x00; 960 * 540 / 2];
+/// let popup = Offscreen::new(10, 10);
+/// blit(&mut fb, &popup, 5, 5, BlendMode::Replace, 0.5, None);
+/// assert_eq!(epd_gfx::get_pixel(&fb, 5, 5), Some(Gray4::GRAY));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 4, 5), Some(Gray4::BLACK));
+/// ```
+pub fn blit<F: Framebuffer + ?Sized>(
+    fb: &mut F,
+    src: &Offscreen,
+    x: u32,
+    y: u32,
+    mode: BlendMode,
+    opacity: f32,
+    transparent: Option<Gray4>,
+) {
+    let opacity = opacity.clamp(0.0, 1.0);
+    if opacity == 0.0 {
+        return;
+    }
+    let area = Rect::new(x, y, src.width, src.height);
+    let Some(visible) = area.intersection(&Rect::new(0, 0, fb.width(), fb.height())) else {
+        return;
+    };
+    for fy in visible.y..visible.bottom() {
+        for fx in visible.x..visible.right() {
+            let Some(color) = src.get_pixel(fx - x, fy - y) else {
+                continue;
+            };
+            if Some(color) == transparent {
+                continue;
+            }
+            let Some(below) = fb.get_pixel(fx, fy) else {
+                continue;
+            };
+            let blended = mix(below, mode.blend(color, below), opacity);
+            if blended != below {
+                fb.draw_pixel(fx, fy, blended);
+            }
+        }
+    }
+}
+
+/// An offscreen buffer with a position and the way it is composited.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub buffer: Offscreen,
+    pub x: u32,
+    pub y: u32,
+    /// Layers with a higher z are drawn on top of those with a lower one.
+    pub z: i32,
+    /// From 0 (invisible) to 1 (opaque).
+    pub opacity: f32,
+    pub blend: BlendMode,
+    /// Pixels of this color are not drawn.
+    pub transparent: Option<Gray4>,
+    pub visible: bool,
+}
+
+impl Layer {
+    /// An opaque, visible layer at (x, y) which replaces what is below.
+    pub fn new(buffer: Offscreen, x: u32, y: u32) -> Self {
+        Self {
+            buffer,
+            x,
+            y,
+            z: 0,
+            opacity: 1.0,
+            blend: BlendMode::Replace,
+            transparent: None,
+            visible: true,
+        }
+    }
+
+    pub fn with_z(self, z: i32) -> Self {
+        Self { z, ..self }
+    }
+
+    pub fn with_opacity(self, opacity: f32) -> Self {
+        Self { opacity, ..self }
+    }
+
+    pub fn with_blend(self, blend: BlendMode) -> Self {
+        Self { blend, ..self }
+    }
+
+    pub fn with_transparent(self, color: Gray4) -> Self {
+        Self {
+            transparent: Some(color),
+            ..self
+        }
+    }
+
+    /// The area covered by the layer, in framebuffer coordinates.
+    pub fn area(&self) -> Rect {
+        Rect::new(self.x, self.y, self.buffer.width, self.buffer.height)
+    }
+
+    /// Composite the layer into `fb`, if it is visible.
+    pub fn draw<F: Framebuffer + ?Sized>(&self, fb: &mut F) {
+        if self.visible {
+            blit(
+                fb,
+                &self.buffer,
+                self.x,
+                self.y,
+                self.blend,
+                self.opacity,
+                self.transparent,
+            );
+        }
+    }
+}
+
+/// Identifies a layer within a [`Layers`] stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LayerId(usize);
+
+/// A stack of layers, composited in z-order.
+/// ```
+/// use epd_gfx::layer::{BlendMode, Layer, Layers, Offscreen};
+/// use epd_gfx::Gray4;
+///
+/// let mut layers = Layers::new();
+/// layers.add(Layer::new(Offscreen::filled(540, 960, Gray4::LIGHT_GRAY), 0, 0));
+/// let shadow = Layer::new(Offscreen::filled(20, 20, Gray4::GRAY), 10, 10)
+///     .with_z(1)
+///     .with_blend(BlendMode::Multiply);
+/// let shadow = layers.add(shadow);
+///
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// layers.composite(&mut fb);
+/// assert_eq!(epd_gfx::get_pixel(&fb, 0, 0), Some(Gray4::LIGHT_GRAY));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 10, 10), Some(Gray4::new(0x6)));
+///
+/// layers.get_mut(shadow).unwrap().visible = false;
+/// layers.composite(&mut fb);
+/// assert_eq!(epd_gfx::get_pixel(&fb, 10, 10), Some(Gray4::LIGHT_GRAY));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Layers {
+    layers: Vec<Option<Layer>>,
+}
+
+impl Layers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, layer: Layer) -> LayerId {
+        self.layers.push(Some(layer));
+        LayerId(self.layers.len() - 1)
+    }
+
+    pub fn remove(&mut self, id: LayerId) -> Option<Layer> {
+        self.layers.get_mut(id.0)?.take()
+    }
+
+    pub fn get(&self, id: LayerId) -> Option<&Layer> {
+        self.layers.get(id.0)?.as_ref()
+    }
+
+    /// Access a layer, e.g. to draw into its buffer or to move it.
+    pub fn get_mut(&mut self, id: LayerId) -> Option<&mut Layer> {
+        self.layers.get_mut(id.0)?.as_mut()
+    }
+
+    /// Iterate over the layers from bottom to top. Layers with the same z
+    /// are ordered by when they were added.
+    pub fn iter(&self) -> impl Iterator<Item = &Layer> {
+        let mut layers: Vec<&Layer> = self.layers.iter().flatten().collect();
+        layers.sort_by_key(|layer| layer.z);
+        layers.into_iter()
+    }
+
+    /// Draw all visible layers into `fb`, from bottom to top.
+    pub fn composite<F: Framebuffer + ?Sized>(&self, fb: &mut F) {
+        for layer in self.iter() {
+            layer.draw(fb);
+        }
+    }
+
+    /// The area covered by visible layers, in framebuffer coordinates.
+    pub fn bounds(&self) -> Option<Rect> {
+        self.iter()
+            .filter(|layer| layer.visible)
+            .map(Layer::area)
+            .reduce(|a, b| a.union(&b))
+    }
+}
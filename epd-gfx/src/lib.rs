@@ -1,4 +1,180 @@
+pub mod canvas;
+pub mod color;
+pub mod diff;
+pub mod dirty;
+pub mod enhance;
 pub mod font;
+pub mod gray8;
+pub mod image;
+pub mod layer;
+pub mod mask;
+pub mod mono;
+pub mod paint;
+pub mod rect;
+pub mod render;
+pub mod view;
+
+pub use color::Gray4;
+
+/// Width of the display panel in its native landscape orientation.
+pub const EPD_WIDTH: u32 = 960;
+/// Height of the display panel in its native landscape orientation.
+pub const EPD_HEIGHT: u32 = 540;
+
+/// Something that can be drawn on with the functions of this crate.
+///
+/// Coordinates are given in portrait orientation (see [`to_landscape`]) and
+/// colors as one of the 16 gray levels of the display, see [`Gray4`].
+pub trait Framebuffer {
+    /// Set a single pixel. Pixels outside of the display are ignored.
+    fn draw_pixel(&mut self, x: u32, y: u32, color: Gray4);
+
+    /// Read back the color of a pixel, if it lies on the display.
+    fn get_pixel(&self, x: u32, y: u32) -> Option<Gray4>;
+
+    /// Width in portrait orientation.
+    fn width(&self) -> u32 {
+        EPD_HEIGHT
+    }
+
+    /// Height in portrait orientation.
+    fn height(&self) -> u32 {
+        EPD_WIDTH
+    }
+
+    /// Fill a rectangle, clipped to the display.
+    ///
+    /// This draws pixel by pixel, framebuffers override it to fill whole
+    /// bytes along the rows of the rotated rectangle.
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Gray4) {
+        let x_end = x.saturating_add(w).min(self.width());
+        let y_end = y.saturating_add(h).min(self.height());
+        for yy in y..y_end {
+            for xx in x..x_end {
+                self.draw_pixel(xx, yy, color);
+            }
+        }
+    }
+}
+
+/// A 4 bit-per-pixel framebuffer as used by epdiy's `MODE_PACKING_2PPB`.
+impl Framebuffer for [u8] {
+    fn draw_pixel(&mut self, x: u32, y: u32, color: Gray4) {
+        // EPD expects framebuffer for landscape display (WIDTH > HEIGHT)
+        if let Some((x, y)) = to_landscape(x, y) {
+            // x is [0, 960) and y is [0, 540)
+            let fb_index = ((y * EPD_WIDTH + x) / 2) as usize;
+            let (left, right) = split_byte(self[fb_index]);
+            if x % 2 == 0 {
+                self[fb_index] = join_bytes(left, color.level());
+            } else {
+                self[fb_index] = join_bytes(color.level(), right);
+            }
+        }
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Option<Gray4> {
+        let (x, y) = to_landscape(x, y)?;
+        let byte = self[((y * EPD_WIDTH + x) / 2) as usize];
+        Some(if x % 2 == 0 {
+            Gray4::from_low_nibble(byte)
+        } else {
+            Gray4::from_high_nibble(byte)
+        })
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Gray4) {
+        if let Some((x0, x1, y0, y1)) = landscape_rect(x, y, w, h) {
+            let row_bytes = (EPD_WIDTH / 2) as usize;
+            for row in self
+                .chunks_exact_mut(row_bytes)
+                .take(y1 as usize)
+                .skip(y0 as usize)
+            {
+                fill_nibbles(row, x0 as usize, x1 as usize, color);
+            }
+        }
+    }
+}
+
+/// Fill the pixels `x0..x1` of a 4bpp row, setting two pixels at once where
+/// possible.
+pub(crate) fn fill_nibbles(row: &mut [u8], mut x0: usize, mut x1: usize, color: Gray4) {
+    let color = color.level();
+    if x0 % 2 == 1 && x0 < x1 {
+        row[x0 / 2] = (row[x0 / 2] & 0x0F) | (color << 4);
+        x0 += 1;
+    }
+    if x1 % 2 == 1 && x0 < x1 {
+        row[x1 / 2] = (row[x1 / 2] & 0xF0) | color;
+        x1 -= 1;
+    }
+    if x0 < x1 {
+        row[x0 / 2..x1 / 2].fill(color * 0x11);
+    }
+}
+
+/// Transform a rectangle to landscape coordinates, clipped to the display.
+/// Returns the landscape columns `x0..x1` and rows `y0..y1` it covers, or
+/// `None` if nothing of it is visible.
+pub(crate) fn landscape_rect(x: u32, y: u32, w: u32, h: u32) -> Option<(u32, u32, u32, u32)> {
+    let x_end = x.saturating_add(w).min(EPD_HEIGHT);
+    let y_end = y.saturating_add(h).min(EPD_WIDTH);
+    if x >= x_end || y >= y_end {
+        return None;
+    }
+    Some((EPD_WIDTH - y_end, EPD_WIDTH - y, x, x_end))
+}
+
+impl<const N: usize> Framebuffer for [u8; N] {
+    fn draw_pixel(&mut self, x: u32, y: u32, color: Gray4) {
+        self[..].draw_pixel(x, y, color);
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Option<Gray4> {
+        self[..].get_pixel(x, y)
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Gray4) {
+        self[..].fill_rect(x, y, w, h, color);
+    }
+}
+
+impl Framebuffer for Vec<u8> {
+    fn draw_pixel(&mut self, x: u32, y: u32, color: Gray4) {
+        self[..].draw_pixel(x, y, color);
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Option<Gray4> {
+        self[..].get_pixel(x, y)
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Gray4) {
+        self[..].fill_rect(x, y, w, h, color);
+    }
+}
+
+impl<F: Framebuffer + ?Sized> Framebuffer for &mut F {
+    fn draw_pixel(&mut self, x: u32, y: u32, color: Gray4) {
+        (**self).draw_pixel(x, y, color);
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Option<Gray4> {
+        (**self).get_pixel(x, y)
+    }
+
+    fn width(&self) -> u32 {
+        (**self).width()
+    }
+
+    fn height(&self) -> u32 {
+        (**self).height()
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Gray4) {
+        (**self).fill_rect(x, y, w, h, color);
+    }
+}
 
 /// Split a framebuffer byte into two pixels of 4 significant bits each.
 /// ```
@@ -33,46 +209,255 @@ pub fn join_bytes(left: u8, right: u8) -> u8 {
 /// assert_eq!(epd_gfx::to_landscape(539,959), Some((0,539)));
 /// ```
 pub fn to_landscape(x: u32, y: u32) -> Option<(u32, u32)> {
-    if x >= 540 || y >= 960 {
+    if x >= EPD_HEIGHT || y >= EPD_WIDTH {
         return None;
     }
-    return Some((960 - y - 1, x));
+    return Some((EPD_WIDTH - y - 1, x));
 }
 
-pub fn draw_pixel(fb: &mut [u8], x: u32, y: u32, color: u8) {
-    // EPD expects framebuffer for landscape display (WIDTH > HEIGHT)
-    if let Some((x, y)) = to_landscape(x, y) {
-        // x is [0, 960) and y is [0, 540)
-        let fb_index = ((y * 960 + x) / 2) as usize;
-        let (left, right) = split_byte(fb[fb_index]);
-        if x % 2 == 0 {
-            fb[fb_index] = join_bytes(left, color);
-        } else {
-            fb[fb_index] = join_bytes(color, right);
-        }
+/// Transform a point (x,y) from landscape to portrait coordinates, if possible.
+/// This is the inverse of [`to_landscape`].
+/// ```
+/// assert_eq!(epd_gfx::from_landscape(859,50), Some((50,100)));
+/// assert_eq!(epd_gfx::from_landscape(960,0), None);
+/// ```
+pub fn from_landscape(x: u32, y: u32) -> Option<(u32, u32)> {
+    if x >= EPD_WIDTH || y >= EPD_HEIGHT {
+        return None;
     }
+    Some((y, EPD_WIDTH - x - 1))
+}
+
+pub fn draw_pixel<F: Framebuffer + ?Sized>(fb: &mut F, x: u32, y: u32, color: Gray4) {
+    fb.draw_pixel(x, y, color);
+}
+
+/// Get the color of a pixel, like epdiy's `epd_get_pixel`.
+/// ```
+/// use epd_gfx::Gray4;
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// epd_gfx::draw_pixel(&mut fb, 10, 20, Gray4::new(0x3));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 10, 20), Some(Gray4::new(0x3)));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 11, 20), Some(Gray4::WHITE));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 540, 0), None);
+/// ```
+pub fn get_pixel<F: Framebuffer + ?Sized>(fb: &F, x: u32, y: u32) -> Option<Gray4> {
+    fb.get_pixel(x, y)
+}
+
+/// Set every pixel of a 4bpp framebuffer to `color`.
+/// ```
+/// use epd_gfx::Gray4;
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// epd_gfx::set_all(&mut fb, Gray4::GRAY);
+/// assert!(fb.iter().all(|&byte| byte == 0x88));
+/// ```
+pub fn set_all(fb: &mut [u8], color: Gray4) {
+    fb.fill(color.packed());
+}
+
+pub fn draw_hline<F: Framebuffer + ?Sized>(fb: &mut F, x: u32, y: u32, length: u32, color: Gray4) {
+    fb.fill_rect(x, y, length, 1, color);
+}
+
+pub fn draw_vline<F: Framebuffer + ?Sized>(fb: &mut F, x: u32, y: u32, length: u32, color: Gray4) {
+    fb.fill_rect(x, y, 1, length, color);
 }
 
-pub fn set_all(fb: &mut [u8], color: u8) {
-    fb.iter_mut().for_each(|x| *x = color);
+/// Fill a rectangle.
+/// ```
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// epd_gfx::fill_rect(&mut fb, 0, 0, 2, 3, epd_gfx::Gray4::BLACK);
+/// assert_eq!(&fb[478..480], &[0x0F, 0x00]);
+/// assert_eq!(&fb[958..960], &[0x0F, 0x00]);
+/// assert_eq!(fb[1438], 0xFF);
+/// ```
+pub fn fill_rect<F: Framebuffer + ?Sized>(
+    fb: &mut F,
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+    color: Gray4,
+) {
+    fb.fill_rect(x, y, w, h, color);
 }
 
-pub fn draw_hline(fb: &mut [u8], x: u32, y: u32, length: u32, color: u8) {
-    for i in 0..length {
-        let xx = x + i;
-        draw_pixel(fb, xx, y, color);
+/// Invert the colors of a rectangle, e.g. to highlight a selection.
+/// Inverting twice restores the original content.
+/// ```
+/// use epd_gfx::Gray4;
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// epd_gfx::draw_pixel(&mut fb, 11, 10, Gray4::new(0x3));
+/// epd_gfx::invert_rect(&mut fb, 10, 10, 20, 5);
+/// assert_eq!(epd_gfx::get_pixel(&fb, 10, 10), Some(Gray4::BLACK));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 11, 10), Some(Gray4::LIGHT_GRAY));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 30, 10), Some(Gray4::WHITE));
+/// ```
+pub fn invert_rect<F: Framebuffer + ?Sized>(fb: &mut F, x: u32, y: u32, w: u32, h: u32) {
+    let x_end = x.saturating_add(w).min(fb.width());
+    let y_end = y.saturating_add(h).min(fb.height());
+    for yy in y..y_end {
+        for xx in x..x_end {
+            if let Some(color) = fb.get_pixel(xx, yy) {
+                fb.draw_pixel(xx, yy, color.invert());
+            }
+        }
     }
 }
 
-pub fn draw_vline(fb: &mut [u8], x: u32, y: u32, length: u32, color: u8) {
-    for i in 0..length {
-        let yy = y + i;
-        draw_pixel(fb, x, yy, color);
+/// Copy the rectangle at (x, y) within the same framebuffer, so that its top
+/// left corner ends up at (to_x, to_y).
+///
+/// Source and destination may overlap, the pixels are copied in an order
+/// which never reads a pixel that was already overwritten. Parts of either
+/// rectangle outside of the framebuffer are not copied.
+/// ```
+/// use epd_gfx::Gray4;
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// epd_gfx::draw_hline(&mut fb, 0, 0, 4, Gray4::BLACK);
+/// epd_gfx::draw_pixel(&mut fb, 4, 0, Gray4::GRAY);
+/// epd_gfx::copy_rect(&mut fb, 0, 0, 5, 1, 2, 0);
+/// assert_eq!(epd_gfx::get_pixel(&fb, 5, 0), Some(Gray4::BLACK));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 6, 0), Some(Gray4::GRAY));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 7, 0), Some(Gray4::WHITE));
+/// ```
+pub fn copy_rect<F: Framebuffer + ?Sized>(
+    fb: &mut F,
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+    to_x: u32,
+    to_y: u32,
+) {
+    let (width, height) = (fb.width(), fb.height());
+    let w = w
+        .min(width.saturating_sub(x))
+        .min(width.saturating_sub(to_x));
+    let h = h
+        .min(height.saturating_sub(y))
+        .min(height.saturating_sub(to_y));
+
+    // Copy away from the direction of movement, so that overlapping source
+    // pixels are read before they are overwritten.
+    let copy_row = |fb: &mut F, row: u32| {
+        let copy = |fb: &mut F, col: u32| {
+            if let Some(color) = fb.get_pixel(x + col, y + row) {
+                fb.draw_pixel(to_x + col, to_y + row, color);
+            }
+        };
+        if to_x > x {
+            (0..w).rev().for_each(|col| copy(fb, col));
+        } else {
+            (0..w).for_each(|col| copy(fb, col));
+        }
+    };
+    if to_y > y {
+        (0..h).rev().for_each(|row| copy_row(fb, row));
+    } else {
+        (0..h).for_each(|row| copy_row(fb, row));
     }
 }
 
-pub fn fill_rect(fb: &mut [u8], x: u32, y: u32, w: u32, h: u32, color: u8) {
-    for i in y..y + h {
-        draw_hline(fb, x, i, w, color);
+/// Move the content of a rectangle by (dx, dy) without redrawing it, e.g. to
+/// scroll a terminal by a line. Content moved out of the rectangle is lost,
+/// the area which becomes uncovered is filled with `background`.
+/// ```
+/// use epd_gfx::Gray4;
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// epd_gfx::draw_hline(&mut fb, 0, 10, 100, Gray4::BLACK);
+/// epd_gfx::draw_hline(&mut fb, 0, 20, 100, Gray4::GRAY);
+/// epd_gfx::scroll_rect(&mut fb, 0, 0, 100, 50, 0, -10, Gray4::WHITE);
+/// assert_eq!(epd_gfx::get_pixel(&fb, 50, 0), Some(Gray4::BLACK));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 50, 10), Some(Gray4::GRAY));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 50, 20), Some(Gray4::WHITE));
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub fn scroll_rect<F: Framebuffer + ?Sized>(
+    fb: &mut F,
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+    dx: i32,
+    dy: i32,
+    background: Gray4,
+) {
+    let w = w.min(fb.width().saturating_sub(x));
+    let h = h.min(fb.height().saturating_sub(y));
+    let (shift_x, shift_y) = (dx.unsigned_abs(), dy.unsigned_abs());
+    if shift_x >= w || shift_y >= h {
+        fb.fill_rect(x, y, w, h, background);
+        return;
+    }
+
+    let (from_x, to_x) = if dx < 0 {
+        (x + shift_x, x)
+    } else {
+        (x, x + shift_x)
+    };
+    let (from_y, to_y) = if dy < 0 {
+        (y + shift_y, y)
+    } else {
+        (y, y + shift_y)
+    };
+    copy_rect(fb, from_x, from_y, w - shift_x, h - shift_y, to_x, to_y);
+
+    // The uncovered strips along the edges the content moved away from
+    let strip_x = if dx < 0 { x + w - shift_x } else { x };
+    let strip_y = if dy < 0 { y + h - shift_y } else { y };
+    fb.fill_rect(strip_x, y, shift_x, h, background);
+    fb.fill_rect(x, strip_y, w, shift_y, background);
+}
+
+/// Fill the area of same colored pixels around (x, y) with `color`, like the
+/// bucket tool of a paint program. Pixels are connected horizontally and
+/// vertically, but not diagonally.
+/// ```
+/// use epd_gfx::Gray4;
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// epd_gfx::fill_rect(&mut fb, 10, 10, 20, 20, Gray4::BLACK);
+/// epd_gfx::fill_rect(&mut fb, 11, 11, 18, 18, Gray4::WHITE);
+/// epd_gfx::flood_fill(&mut fb, 20, 20, Gray4::GRAY);
+/// assert_eq!(epd_gfx::get_pixel(&fb, 11, 28), Some(Gray4::GRAY));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 10, 10), Some(Gray4::BLACK));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 5, 5), Some(Gray4::WHITE));
+/// ```
+pub fn flood_fill<F: Framebuffer + ?Sized>(fb: &mut F, x: u32, y: u32, color: Gray4) {
+    let target = match fb.get_pixel(x, y) {
+        Some(target) if target != color => target,
+        _ => return,
+    };
+    let (width, height) = (fb.width(), fb.height());
+
+    // Each seed is a pixel of a span which still has to be filled
+    let mut seeds = vec![(x, y)];
+    while let Some((x, y)) = seeds.pop() {
+        if fb.get_pixel(x, y) != Some(target) {
+            continue;
+        }
+        let mut x0 = x;
+        while x0 > 0 && fb.get_pixel(x0 - 1, y) == Some(target) {
+            x0 -= 1;
+        }
+        let mut x1 = x + 1;
+        while x1 < width && fb.get_pixel(x1, y) == Some(target) {
+            x1 += 1;
+        }
+        fb.fill_rect(x0, y, x1 - x0, 1, color);
+
+        // Add one seed for each run of target pixels above and below the span
+        let neighbours = [y.checked_sub(1), Some(y + 1).filter(|&y| y < height)];
+        for ny in neighbours.into_iter().flatten() {
+            let mut in_run = false;
+            for nx in x0..x1 {
+                let matches = fb.get_pixel(nx, ny) == Some(target);
+                if matches && !in_run {
+                    seeds.push((nx, ny));
+                }
+                in_run = matches;
+            }
+        }
     }
 }
@@ -0,0 +1,266 @@
+//! Drawing through grayscale masks.
+//!
+//! A [`Mask`] holds an 8 bit coverage value per pixel. Wrapping a framebuffer
+//! in [`Masked`] makes every drawing operation only replace as much of a
+//! pixel as the mask covers, e.g. to round the corners of a photo or to fade
+//! out the bottom of a list. With coverage values of only 0 and 255, a mask
+//! works as a stencil.
+
+use crate::{Framebuffer, Gray4};
+
+/// Full coverage: the source replaces the destination pixel.
+pub const OPAQUE: u8 = 0xFF;
+
+/// An 8 bit coverage mask of any size.
+///
+/// Masks are framebuffers themselves, so that they can be drawn with the
+/// normal drawing API: [`Gray4::WHITE`] means full coverage and
+/// [`Gray4::BLACK`] none, like a luminance mask in SVG.
+/// ```
+/// use epd_gfx::mask::Mask;
+/// use epd_gfx::Gray4;
+/// let mut mask = Mask::new(10, 10);
+/// epd_gfx::fill_rect(&mut mask, 0, 0, 5, 10, Gray4::WHITE);
+/// assert_eq!(mask.coverage(4, 0), 0xFF);
+/// assert_eq!(mask.coverage(5, 0), 0x00);
+/// assert_eq!(mask.coverage(20, 0), 0x00);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mask {
+    width: u32,
+    height: u32,
+    buf: Vec<u8>,
+}
+
+impl Mask {
+    /// A mask which covers nothing.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::filled(width, height, 0)
+    }
+
+    /// A mask with the same coverage everywhere.
+    pub fn filled(width: u32, height: u32, coverage: u8) -> Self {
+        Self {
+            width,
+            height,
+            buf: vec![coverage; width as usize * height as usize],
+        }
+    }
+
+    /// Compute the coverage of each pixel.
+    /// ```
+    /// use epd_gfx::mask::Mask;
+    /// // Fade out towards the bottom
+    /// let fade = Mask::from_fn(100, 51, |_, y| (255 - y * 5) as u8);
+    /// assert_eq!((fade.coverage(0, 0), fade.coverage(0, 50)), (255, 5));
+    /// ```
+    pub fn from_fn(width: u32, height: u32, f: impl Fn(u32, u32) -> u8) -> Self {
+        let mut mask = Self::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let i = mask.index(x, y);
+                mask.buf[i] = f(x, y);
+            }
+        }
+        mask
+    }
+
+    /// Use the gray levels of a framebuffer area as coverage. Pixels outside
+    /// of `fb` are not covered.
+    pub fn from_framebuffer<F: Framebuffer + ?Sized>(
+        fb: &F,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self::from_fn(width, height, |mx, my| {
+            fb.get_pixel(x.saturating_add(mx), y.saturating_add(my))
+                .map_or(0, Gray4::to_luma)
+        })
+    }
+
+    /// The coverage at (x, y), or 0 outside of the mask.
+    pub fn coverage(&self, x: u32, y: u32) -> u8 {
+        if x < self.width && y < self.height {
+            self.buf[self.index(x, y)]
+        } else {
+            0
+        }
+    }
+
+    pub fn set_coverage(&mut self, x: u32, y: u32, coverage: u8) {
+        if x < self.width && y < self.height {
+            let i = self.index(x, y);
+            self.buf[i] = coverage;
+        }
+    }
+
+    /// Swap covered and uncovered areas.
+    pub fn invert(&mut self) {
+        self.buf.iter_mut().for_each(|c| *c = OPAQUE - *c);
+    }
+
+    /// Combine with another mask placed at (x, y), keeping only what both
+    /// cover.
+    pub fn intersect(&mut self, other: &Mask, x: u32, y: u32) {
+        for my in 0..self.height {
+            for mx in 0..self.width {
+                let theirs = match (mx.checked_sub(x), my.checked_sub(y)) {
+                    (Some(ox), Some(oy)) => other.coverage(ox, oy),
+                    _ => 0,
+                };
+                let i = self.index(mx, my);
+                let ours = &mut self.buf[i];
+                *ours = ((*ours as u16 * theirs as u16 + 127) / 255) as u8;
+            }
+        }
+    }
+
+    /// The index of (x, y) in `buf`, computed in `usize` so that large masks
+    /// do not overflow.
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+}
+
+impl Framebuffer for Mask {
+    fn draw_pixel(&mut self, x: u32, y: u32, color: Gray4) {
+        self.set_coverage(x, y, color.to_luma());
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Option<Gray4> {
+        (x < self.width && y < self.height).then(|| Gray4::from_high_nibble(self.coverage(x, y)))
+    }
+
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Gray4) {
+        let x_end = x.saturating_add(w).min(self.width);
+        let y_end = y.saturating_add(h).min(self.height);
+        if x >= x_end {
+            return;
+        }
+        let rows = self.buf.chunks_exact_mut(self.width as usize);
+        for row in rows.take(y_end as usize).skip(y as usize) {
+            row[x as usize..x_end as usize].fill(color.to_luma());
+        }
+    }
+}
+
+/// Mix `color` into `old` by `coverage`.
+fn blend(old: Gray4, color: Gray4, coverage: u8) -> Gray4 {
+    let (old, color, coverage) = (old.level() as u16, color.level() as u16, coverage as u16);
+    Gray4::new(((old * (OPAQUE as u16 - coverage) + color * coverage + 127) / 255) as u8)
+}
+
+/// A framebuffer which draws through a mask placed at (x, y).
+///
+/// Drawing replaces each pixel by as much as the mask covers it. Everything
+/// outside of the mask is left alone.
+/// ```
+/// use epd_gfx::canvas::Canvas;
+/// use epd_gfx::mask::{Mask, Masked};
+/// use epd_gfx::Gray4;
+///
+/// // A mask with rounded corners
+/// let mut mask = Mask::new(100, 100);
+/// let mut canvas = Canvas::new(&mut mask);
+/// canvas.set_fill(Gray4::WHITE);
+/// canvas.fill_rect(10.0, 0.0, 80.0, 100.0);
+/// canvas.fill_rect(0.0, 10.0, 100.0, 80.0);
+/// for (x, y) in [(10.0, 10.0), (90.0, 10.0), (10.0, 90.0), (90.0, 90.0)] {
+///     canvas.fill_circle(x, y, 10.0);
+/// }
+///
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// let mut masked = Masked::new(&mut fb, &mask, 20, 20);
+/// epd_gfx::fill_rect(&mut masked, 0, 0, 540, 960, Gray4::BLACK);
+/// assert_eq!(epd_gfx::get_pixel(&fb, 20, 20), Some(Gray4::WHITE));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 22, 40), Some(Gray4::BLACK));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 120, 40), Some(Gray4::WHITE));
+/// ```
+#[derive(Debug)]
+pub struct Masked<'m, F> {
+    fb: F,
+    mask: &'m Mask,
+    x: u32,
+    y: u32,
+}
+
+impl<'m, F: Framebuffer> Masked<'m, F> {
+    pub fn new(fb: F, mask: &'m Mask, x: u32, y: u32) -> Self {
+        Self { fb, mask, x, y }
+    }
+
+    pub fn into_inner(self) -> F {
+        self.fb
+    }
+
+    /// The coverage of the mask at (x, y) in framebuffer coordinates.
+    fn coverage(&self, x: u32, y: u32) -> u8 {
+        match (x.checked_sub(self.x), y.checked_sub(self.y)) {
+            (Some(mx), Some(my)) => self.mask.coverage(mx, my),
+            _ => 0,
+        }
+    }
+}
+
+impl<F: Framebuffer> Framebuffer for Masked<'_, F> {
+    fn draw_pixel(&mut self, x: u32, y: u32, color: Gray4) {
+        match self.coverage(x, y) {
+            0 => {}
+            OPAQUE => self.fb.draw_pixel(x, y, color),
+            coverage => {
+                if let Some(old) = self.fb.get_pixel(x, y) {
+                    self.fb.draw_pixel(x, y, blend(old, color, coverage));
+                }
+            }
+        }
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Option<Gray4> {
+        self.fb.get_pixel(x, y)
+    }
+
+    fn width(&self) -> u32 {
+        self.fb.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.fb.height()
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Gray4) {
+        // Only the part covered by the mask can change
+        let x0 = x.max(self.x);
+        let y0 = y.max(self.y);
+        let x1 = x
+            .saturating_add(w)
+            .min(self.x.saturating_add(self.mask.width))
+            .min(self.fb.width());
+        let y1 = y
+            .saturating_add(h)
+            .min(self.y.saturating_add(self.mask.height))
+            .min(self.fb.height());
+        for yy in y0..y1 {
+            for xx in x0..x1 {
+                self.draw_pixel(xx, yy, color);
+            }
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! 1 bit-per-pixel framebuffers for fast monochrome updates.
+//!
+//! The buffer layout matches epdiy's `MODE_PACKING_8PPB`: pixels are stored
+//! in landscape orientation, eight per byte, with `0` meaning black and `1`
+//! white. Like the nibbles of the 4bpp framebuffer, the leftmost pixel of a
+//! byte is the least significant bit. Such a buffer is an eighth of the size
+//! of a 4bpp framebuffer and can be drawn with `MODE_EPDIY_MONOCHROME`.
+
+use crate::color::Gray1;
+use crate::{landscape_rect, to_landscape, Framebuffer, Gray4, EPD_HEIGHT, EPD_WIDTH};
+
+/// Size of a 1 bit-per-pixel framebuffer in bytes.
+pub const MONO_FB_SIZE: usize = (EPD_WIDTH * EPD_HEIGHT / 8) as usize;
+
+/// Gray levels from this threshold upwards are drawn white.
+pub const DEFAULT_THRESHOLD: Gray4 = Gray4::GRAY;
+
+/// A 1 bit-per-pixel framebuffer.
+///
+/// It accepts the same gray levels as the 4bpp framebuffer, levels
+/// below [`DEFAULT_THRESHOLD`] become black and all others white.
+/// ```
+/// use epd_gfx::mono::Mono;
+/// use epd_gfx::color::Gray1;
+/// use epd_gfx::Gray4;
+/// let mut mono = Mono::new();
+/// epd_gfx::fill_rect(&mut mono, 0, 0, 1, 8, Gray4::BLACK);
+/// assert_eq!(mono.as_bytes()[119], 0x00);
+/// assert_eq!(mono.gray1(0, 8), Some(Gray1::WHITE));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Mono<B = Vec<u8>> {
+    buf: B,
+}
+
+impl Mono<Vec<u8>> {
+    /// Allocate a new, white framebuffer.
+    pub fn new() -> Self {
+        Self {
+            buf: vec![0xFF; MONO_FB_SIZE],
+        }
+    }
+
+    /// Convert a 4bpp framebuffer, drawing gray values of at least
+    /// `threshold` white.
+    pub fn from_4bpp(fb: &[u8], threshold: Gray4) -> Self {
+        let mut mono = Self::new();
+        pack_4bpp(fb, &mut mono.buf, threshold);
+        mono
+    }
+}
+
+impl Default for Mono<Vec<u8>> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Mono<B> {
+    /// Use an existing buffer of [`MONO_FB_SIZE`] bytes, e.g. one allocated
+    /// in DMA capable memory.
+    pub fn from_buffer(buf: B) -> Self {
+        assert_eq!(buf.as_ref().len(), MONO_FB_SIZE, "invalid buffer size");
+        Self { buf }
+    }
+
+    /// The packed data, as expected by `epd_draw_base` with
+    /// `MODE_PACKING_8PPB`.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.buf.as_ref()
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        self.buf.as_mut()
+    }
+
+    pub fn into_inner(self) -> B {
+        self.buf
+    }
+
+    /// The color of the pixel at (x, y), if it lies on the display.
+    pub fn gray1(&self, x: u32, y: u32) -> Option<Gray1> {
+        let (x, y) = to_landscape(x, y)?;
+        let index = y * EPD_WIDTH + x;
+        let white = self.as_bytes()[index as usize / 8] & (1 << (index % 8)) != 0;
+        Some(if white { Gray1::WHITE } else { Gray1::BLACK })
+    }
+
+    /// Expand into a 4bpp framebuffer of black and white pixels.
+    pub fn to_4bpp(&self, fb: &mut [u8]) {
+        unpack_to_4bpp(self.as_bytes(), fb);
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Framebuffer for Mono<B> {
+    fn draw_pixel(&mut self, x: u32, y: u32, color: Gray4) {
+        if let Some((x, y)) = to_landscape(x, y) {
+            let index = y * EPD_WIDTH + x;
+            let byte = &mut self.buf.as_mut()[index as usize / 8];
+            let bit = 1 << (index % 8);
+            if color >= DEFAULT_THRESHOLD {
+                *byte |= bit;
+            } else {
+                *byte &= !bit;
+            }
+        }
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Option<Gray4> {
+        self.gray1(x, y).map(Gray4::from)
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Gray4) {
+        if let Some((x0, x1, y0, y1)) = landscape_rect(x, y, w, h) {
+            let rows = self.buf.as_mut().chunks_exact_mut((EPD_WIDTH / 8) as usize);
+            for row in rows.take(y1 as usize).skip(y0 as usize) {
+                fill_bits(row, x0 as usize, x1 as usize, color >= DEFAULT_THRESHOLD);
+            }
+        }
+    }
+}
+
+/// Fill the pixels `x0..x1` of a 1bpp row, setting whole bytes where possible.
+fn fill_bits(row: &mut [u8], x0: usize, x1: usize, white: bool) {
+    let set = |row: &mut [u8], x: usize| {
+        if white {
+            row[x / 8] |= 1 << (x % 8);
+        } else {
+            row[x / 8] &= !(1 << (x % 8));
+        }
+    };
+    let mut x = x0;
+    while x < x1 && !x.is_multiple_of(8) {
+        set(row, x);
+        x += 1;
+    }
+    let full = (x1 - x) / 8;
+    row[x / 8..x / 8 + full].fill(if white { 0xFF } else { 0x00 });
+    x += full * 8;
+    while x < x1 {
+        set(row, x);
+        x += 1;
+    }
+}
+
+/// Convert a 4bpp buffer into a 1bpp buffer of an eighth the size.
+/// Gray values of at least `threshold` become white.
+/// ```
+/// let mut mono = [0u8; 1];
+/// epd_gfx::mono::pack_4bpp(&[0xF0, 0x8F, 0x77, 0xFF], &mut mono, epd_gfx::Gray4::GRAY);
+/// assert_eq!(mono[0], 0b1100_1110);
+/// ```
+pub fn pack_4bpp(src: &[u8], dst: &mut [u8], threshold: Gray4) {
+    let threshold = threshold.level();
+    assert_eq!(src.len(), dst.len() * 4, "buffer sizes do not match");
+    for (out, chunk) in dst.iter_mut().zip(src.chunks_exact(4)) {
+        let mut bits = 0;
+        for (i, &byte) in chunk.iter().enumerate() {
+            if byte & 0x0F >= threshold {
+                bits |= 1 << (2 * i);
+            }
+            if byte >> 4 >= threshold {
+                bits |= 1 << (2 * i + 1);
+            }
+        }
+        *out = bits;
+    }
+}
+
+/// Convert a 1bpp buffer into a 4bpp buffer with black (`0x0`) and white
+/// (`0xF`) pixels.
+/// ```
+/// let mut fb = [0u8; 4];
+/// epd_gfx::mono::unpack_to_4bpp(&[0b1100_0110], &mut fb);
+/// assert_eq!(fb, [0xF0, 0x0F, 0x00, 0xFF]);
+/// ```
+pub fn unpack_to_4bpp(src: &[u8], dst: &mut [u8]) {
+    assert_eq!(src.len() * 4, dst.len(), "buffer sizes do not match");
+    for (&bits, chunk) in src.iter().zip(dst.chunks_exact_mut(4)) {
+        for (i, byte) in chunk.iter_mut().enumerate() {
+            let low = if bits & (1 << (2 * i)) != 0 {
+                0x0F
+            } else {
+                0x00
+            };
+            let high = if bits & (1 << (2 * i + 1)) != 0 {
+                0xF0
+            } else {
+                0x00
+            };
+            *byte = low | high;
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! Paints which fill shapes with more than a single gray level.
+//!
+//! Gradients blend between gray levels continuously. With only 16 gray
+//! levels they show visible bands, which ordered dithering breaks up.
+//! Patterns repeat an 8x8 tile of two colors, which also works on the
+//! monochrome framebuffer.
+//!
+//! Paints are positioned in framebuffer coordinates. [`crate::canvas::Canvas`]
+//! moves them along with its transform.
+
+use crate::canvas::Transform;
+use crate::{Framebuffer, Gray4};
+
+/// A 4x4 Bayer matrix for ordered dithering.
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// The geometry of a gradient, which maps each point to a position between
+/// 0 and 1 along the gradient.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientShape {
+    /// Changes along the line from `start` to `end`.
+    Linear { start: (f32, f32), end: (f32, f32) },
+    /// Changes with the distance from `center`, up to `radius`.
+    Radial { center: (f32, f32), radius: f32 },
+    /// Changes with the angle around `center`, clockwise from `angle`
+    /// (in radians, 0 pointing right).
+    Conic { center: (f32, f32), angle: f32 },
+}
+
+impl GradientShape {
+    /// The position of point (x, y) along the gradient, clamped to 0..=1.
+    fn position(&self, x: f32, y: f32) -> f32 {
+        let t = match *self {
+            GradientShape::Linear { start, end } => {
+                let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+                let length = dx * dx + dy * dy;
+                if length == 0.0 {
+                    return 0.0;
+                }
+                ((x - start.0) * dx + (y - start.1) * dy) / length
+            }
+            GradientShape::Radial { center, radius } => {
+                if radius <= 0.0 {
+                    return 1.0;
+                }
+                (x - center.0).hypot(y - center.1) / radius
+            }
+            GradientShape::Conic { center, angle } => {
+                let theta = (y - center.1).atan2(x - center.0) - angle;
+                theta.rem_euclid(std::f32::consts::TAU) / std::f32::consts::TAU
+            }
+        };
+        t.clamp(0.0, 1.0)
+    }
+
+    fn transformed(&self, t: &Transform) -> GradientShape {
+        let scale = (t.scale_x.abs() + t.scale_y.abs()) / 2.0;
+        let apply = |(x, y): (f32, f32)| t.apply(x, y);
+        match *self {
+            GradientShape::Linear { start, end } => GradientShape::Linear {
+                start: apply(start),
+                end: apply(end),
+            },
+            GradientShape::Radial { center, radius } => GradientShape::Radial {
+                center: apply(center),
+                radius: radius * scale,
+            },
+            GradientShape::Conic { center, angle } => GradientShape::Conic {
+                center: apply(center),
+                angle,
+            },
+        }
+    }
+}
+
+/// A gradient between gray levels at given positions.
+/// ```
+/// use epd_gfx::paint::Gradient;
+/// use epd_gfx::Gray4;
+/// let gradient = Gradient::linear((0.0, 0.0), (16.0, 0.0), Gray4::BLACK, Gray4::WHITE);
+/// assert_eq!(gradient.color_at(0, 0), Gray4::BLACK);
+/// assert_eq!(gradient.color_at(7, 0), Gray4::new(0x7));
+/// assert_eq!(gradient.color_at(100, 0), Gray4::WHITE);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    shape: GradientShape,
+    /// Positions and colors, sorted by position.
+    stops: Vec<(f32, Gray4)>,
+    dither: bool,
+}
+
+impl Gradient {
+    /// A gradient going from `from` at position 0 to `to` at position 1,
+    /// without dithering.
+    pub fn new(shape: GradientShape, from: Gray4, to: Gray4) -> Self {
+        Self {
+            shape,
+            stops: vec![(0.0, from), (1.0, to)],
+            dither: false,
+        }
+    }
+
+    pub fn linear(start: (f32, f32), end: (f32, f32), from: Gray4, to: Gray4) -> Self {
+        Self::new(GradientShape::Linear { start, end }, from, to)
+    }
+
+    pub fn radial(center: (f32, f32), radius: f32, from: Gray4, to: Gray4) -> Self {
+        Self::new(GradientShape::Radial { center, radius }, from, to)
+    }
+
+    pub fn conic(center: (f32, f32), angle: f32, from: Gray4, to: Gray4) -> Self {
+        Self::new(GradientShape::Conic { center, angle }, from, to)
+    }
+
+    /// Add a color at `position` (from 0 to 1) in between.
+    /// ```
+    /// use epd_gfx::paint::Gradient;
+    /// use epd_gfx::Gray4;
+    /// let gradient = Gradient::linear((0.0, 0.0), (10.0, 0.0), Gray4::BLACK, Gray4::BLACK)
+    ///     .with_stop(0.5, Gray4::WHITE);
+    /// assert_eq!(gradient.color_at(4, 0), Gray4::new(0xE));
+    /// assert_eq!(gradient.color_at(9, 0), Gray4::new(0x2));
+    /// ```
+    pub fn with_stop(mut self, position: f32, color: Gray4) -> Self {
+        let position = position.clamp(0.0, 1.0);
+        // Insert after stops at the same position, for hard transitions
+        let index = self.stops.partition_point(|&(p, _)| p <= position);
+        self.stops.insert(index, (position, color));
+        self
+    }
+
+    /// Dither between neighboring gray levels instead of rounding.
+    /// ```
+    /// use epd_gfx::paint::Gradient;
+    /// use epd_gfx::Gray4;
+    /// let (from, to) = (Gray4::new(0x7), Gray4::new(0x8));
+    /// let gradient = Gradient::linear((-1000.0, 0.0), (1000.0, 0.0), from, to).with_dither(true);
+    /// // Halfway between both colors, half of the pixels use each of them
+    /// let dark = (0..4)
+    ///     .flat_map(|y| (0..4).map(move |x| (x, y)))
+    ///     .filter(|&(x, y)| gradient.color_at(x, y) == from)
+    ///     .count();
+    /// assert!(dark >= 7 && dark <= 9);
+    /// ```
+    pub fn with_dither(self, dither: bool) -> Self {
+        Self { dither, ..self }
+    }
+
+    pub fn shape(&self) -> GradientShape {
+        self.shape
+    }
+
+    /// The exact gray level at position `t`, from 0.0 to 15.0.
+    fn level(&self, t: f32) -> f32 {
+        let next = self.stops.partition_point(|&(p, _)| p < t);
+        match (self.stops.get(next.wrapping_sub(1)), self.stops.get(next)) {
+            (Some(&(p0, c0)), Some(&(p1, c1))) if p1 > p0 => {
+                let (c0, c1) = (c0.level() as f32, c1.level() as f32);
+                c0 + (c1 - c0) * (t - p0) / (p1 - p0)
+            }
+            (_, Some(&(_, c))) | (Some(&(_, c)), None) => c.level() as f32,
+            (None, None) => 0.0,
+        }
+    }
+
+    /// The color of the pixel at (x, y).
+    pub fn color_at(&self, x: u32, y: u32) -> Gray4 {
+        let t = self.shape.position(x as f32 + 0.5, y as f32 + 0.5);
+        quantize(self.level(t), x, y, self.dither)
+    }
+}
+
+/// Reduce a gray level from 0.0 to 15.0 to a [`Gray4`], optionally with
+/// ordered dithering.
+fn quantize(level: f32, x: u32, y: u32, dither: bool) -> Gray4 {
+    let level = level.clamp(0.0, 15.0);
+    if dither {
+        let threshold = (BAYER_4X4[y as usize % 4][x as usize % 4] as f32 + 0.5) / 16.0;
+        Gray4::from_level_f32((level + threshold).floor())
+    } else {
+        Gray4::from_level_f32(level)
+    }
+}
+
+/// A repeating 8x8 tile of two colors.
+///
+/// Each byte of the tile is a row, from top to bottom. Bit 0 is the leftmost
+/// pixel, like in the monochrome framebuffer, and set bits are drawn in the
+/// foreground color.
+/// ```
+/// use epd_gfx::paint::Pattern;
+/// use epd_gfx::Gray4;
+/// let checker = Pattern::checker(2, Gray4::BLACK, Gray4::WHITE);
+/// assert_eq!(checker.color_at(0, 0), Gray4::BLACK);
+/// assert_eq!(checker.color_at(2, 0), Gray4::WHITE);
+/// assert_eq!(checker.color_at(2, 2), Gray4::BLACK);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pattern {
+    pub tile: [u8; 8],
+    pub foreground: Gray4,
+    pub background: Gray4,
+    /// The framebuffer position of the top left corner of a tile.
+    pub origin: (u32, u32),
+}
+
+impl Pattern {
+    pub fn new(tile: [u8; 8], foreground: Gray4, background: Gray4) -> Self {
+        Self {
+            tile,
+            foreground,
+            background,
+            origin: (0, 0),
+        }
+    }
+
+    /// Horizontal lines every `spacing` pixels. The spacing is limited to 8.
+    pub fn horizontal_hatch(spacing: u32, foreground: Gray4, background: Gray4) -> Self {
+        let spacing = spacing.clamp(1, 8) as usize;
+        let tile = std::array::from_fn(|y| if y % spacing == 0 { 0xFF } else { 0x00 });
+        Self::new(tile, foreground, background)
+    }
+
+    /// Vertical lines every `spacing` pixels. The spacing is limited to 8.
+    pub fn vertical_hatch(spacing: u32, foreground: Gray4, background: Gray4) -> Self {
+        let spacing = spacing.clamp(1, 8);
+        let row = (0..8)
+            .filter(|x| x % spacing == 0)
+            .fold(0u8, |row, x| row | (1 << x));
+        Self::new([row; 8], foreground, background)
+    }
+
+    /// Lines from the bottom left to the top right, 4 pixels apart.
+    pub fn diagonal_hatch(foreground: Gray4, background: Gray4) -> Self {
+        let tile = std::array::from_fn(|y| 0x11u8.rotate_right(y as u32));
+        Self::new(tile, foreground, background)
+    }
+
+    /// Both diagonals, 4 pixels apart.
+    pub fn cross_hatch(foreground: Gray4, background: Gray4) -> Self {
+        let tile =
+            std::array::from_fn(|y| 0x11u8.rotate_right(y as u32) | 0x11u8.rotate_left(y as u32));
+        Self::new(tile, foreground, background)
+    }
+
+    /// Squares of `size` pixels in alternating colors. The size is one of
+    /// 1, 2 or 4.
+    pub fn checker(size: u32, foreground: Gray4, background: Gray4) -> Self {
+        let size = size.clamp(1, 4).next_power_of_two().min(4) as usize;
+        let row = (0..8)
+            .filter(|x| (x / size).is_multiple_of(2))
+            .fold(0u8, |row, x| row | (1 << x));
+        let tile = std::array::from_fn(|y| {
+            if (y / size).is_multiple_of(2) {
+                row
+            } else {
+                !row
+            }
+        });
+        Self::new(tile, foreground, background)
+    }
+
+    /// Single-pixel dots on a 4 pixel grid, offset in every other row.
+    pub fn dots(foreground: Gray4, background: Gray4) -> Self {
+        Self::new([0x11, 0, 0x44, 0, 0x11, 0, 0x44, 0], foreground, background)
+    }
+
+    /// The same pattern with its tiles starting at (x, y).
+    pub fn with_origin(self, x: u32, y: u32) -> Self {
+        Self {
+            origin: (x, y),
+            ..self
+        }
+    }
+
+    /// The color of the pixel at (x, y).
+    pub fn color_at(&self, x: u32, y: u32) -> Gray4 {
+        let tx = x.wrapping_sub(self.origin.0) % 8;
+        let ty = y.wrapping_sub(self.origin.1) % 8;
+        if self.tile[ty as usize] & (1 << tx) != 0 {
+            self.foreground
+        } else {
+            self.background
+        }
+    }
+
+    fn transformed(&self, t: &Transform) -> Pattern {
+        let (x, y) = t.apply(self.origin.0 as f32, self.origin.1 as f32);
+        // Tiles repeat every 8 pixels, so only the offset within one matters
+        self.with_origin(
+            (x.round() as i64).rem_euclid(8) as u32,
+            (y.round() as i64).rem_euclid(8) as u32,
+        )
+    }
+}
+
+/// What a shape is filled with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Paint {
+    Solid(Gray4),
+    Gradient(Gradient),
+    Pattern(Pattern),
+}
+
+impl Paint {
+    /// The color of the pixel at (x, y).
+    pub fn color_at(&self, x: u32, y: u32) -> Gray4 {
+        match self {
+            Paint::Solid(color) => *color,
+            Paint::Gradient(gradient) => gradient.color_at(x, y),
+            Paint::Pattern(pattern) => pattern.color_at(x, y),
+        }
+    }
+
+    /// The paint as seen through `transform`, e.g. to move a gradient along
+    /// with the shape it fills.
+    pub fn transformed(&self, transform: &Transform) -> Paint {
+        match self {
+            Paint::Solid(color) => Paint::Solid(*color),
+            Paint::Gradient(gradient) => Paint::Gradient(Gradient {
+                shape: gradient.shape.transformed(transform),
+                ..gradient.clone()
+            }),
+            Paint::Pattern(pattern) => Paint::Pattern(pattern.transformed(transform)),
+        }
+    }
+
+    /// Fill the pixels `x..x + w` of row `y`.
+    pub(crate) fn fill_span<F: Framebuffer + ?Sized>(&self, fb: &mut F, x: u32, y: u32, w: u32) {
+        match self {
+            Paint::Solid(color) => fb.fill_rect(x, y, w, 1, *color),
+            _ => {
+                for xx in x..x.saturating_add(w).min(fb.width()) {
+                    fb.draw_pixel(xx, y, self.color_at(xx, y));
+                }
+            }
+        }
+    }
+}
+
+impl From<Gray4> for Paint {
+    fn from(color: Gray4) -> Self {
+        Paint::Solid(color)
+    }
+}
+
+impl From<Gradient> for Paint {
+    fn from(gradient: Gradient) -> Self {
+        Paint::Gradient(gradient)
+    }
+}
+
+impl From<Pattern> for Paint {
+    fn from(pattern: Pattern) -> Self {
+        Paint::Pattern(pattern)
+    }
+}
+
+/// Fill a rectangle with a paint.
+/// ```
+/// use epd_gfx::paint::{Paint, Pattern};
+/// use epd_gfx::Gray4;
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// let paint = Paint::from(Pattern::checker(1, Gray4::BLACK, Gray4::WHITE));
+/// epd_gfx::paint::fill_rect(&mut fb, 0, 0, 4, 4, &paint);
+/// assert_eq!(epd_gfx::get_pixel(&fb, 0, 0), Some(Gray4::BLACK));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 1, 0), Some(Gray4::WHITE));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 1, 1), Some(Gray4::BLACK));
+/// ```
+pub fn fill_rect<F: Framebuffer + ?Sized>(
+    fb: &mut F,
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+    paint: &Paint,
+) {
+    if let Paint::Solid(color) = paint {
+        fb.fill_rect(x, y, w, h, *color);
+        return;
+    }
+    for yy in y..y.saturating_add(h).min(fb.height()) {
+        paint.fill_span(fb, x, yy, w);
+    }
+}
@@ -0,0 +1,155 @@
+//! Axis aligned rectangles, in the spirit of epdiy's `EpdRect`.
+
+use crate::{landscape_rect, EPD_HEIGHT, EPD_WIDTH};
+
+/// A rectangular area. Whether it is given in portrait (drawing) or landscape
+/// (panel) coordinates depends on where it is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub const fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The whole display in portrait orientation.
+    pub const fn portrait() -> Self {
+        Self::new(0, 0, EPD_HEIGHT, EPD_WIDTH)
+    }
+
+    /// The whole display in landscape orientation, like `epd_full_screen()`.
+    pub const fn landscape() -> Self {
+        Self::new(0, 0, EPD_WIDTH, EPD_HEIGHT)
+    }
+
+    /// One past the rightmost column.
+    pub fn right(&self) -> u32 {
+        self.x.saturating_add(self.width)
+    }
+
+    /// One past the bottom row.
+    pub fn bottom(&self) -> u32 {
+        self.y.saturating_add(self.height)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// The overlapping area of both rectangles, if there is one.
+    /// ```
+    /// use epd_gfx::rect::Rect;
+    /// let a = Rect::new(0, 0, 10, 10);
+    /// assert_eq!(a.intersection(&Rect::new(5, 8, 10, 10)), Some(Rect::new(5, 8, 5, 2)));
+    /// assert_eq!(a.intersection(&Rect::new(10, 0, 5, 5)), None);
+    /// ```
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        (x < right && y < bottom).then(|| Rect::new(x, y, right - x, bottom - y))
+    }
+
+    /// The smallest rectangle containing both rectangles.
+    /// Empty rectangles are ignored.
+    /// ```
+    /// use epd_gfx::rect::Rect;
+    /// let a = Rect::new(0, 0, 10, 10);
+    /// assert_eq!(a.union(&Rect::new(20, 5, 5, 10)), Rect::new(0, 0, 25, 15));
+    /// assert_eq!(a.union(&Rect::default()), a);
+    /// ```
+    pub fn union(&self, other: &Rect) -> Rect {
+        if other.is_empty() {
+            return *self;
+        }
+        if self.is_empty() {
+            return *other;
+        }
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect::new(
+            x,
+            y,
+            self.right().max(other.right()) - x,
+            self.bottom().max(other.bottom()) - y,
+        )
+    }
+
+    /// Whether the rectangles overlap or are at most `gap` pixels apart.
+    pub fn is_near(&self, other: &Rect, gap: u32) -> bool {
+        self.x <= other.right().saturating_add(gap)
+            && other.x <= self.right().saturating_add(gap)
+            && self.y <= other.bottom().saturating_add(gap)
+            && other.y <= self.bottom().saturating_add(gap)
+    }
+
+    /// Transform a rectangle from portrait to landscape coordinates, clipped to
+    /// the display.
+    /// ```
+    /// use epd_gfx::rect::Rect;
+    /// assert_eq!(Rect::new(10, 20, 30, 40).to_landscape(), Some(Rect::new(900, 10, 40, 30)));
+    /// assert_eq!(Rect::new(600, 0, 10, 10).to_landscape(), None);
+    /// ```
+    pub fn to_landscape(&self) -> Option<Rect> {
+        let (x0, x1, y0, y1) = landscape_rect(self.x, self.y, self.width, self.height)?;
+        Some(Rect::new(x0, y0, x1 - x0, y1 - y0))
+    }
+
+    /// Transform a rectangle from landscape to portrait coordinates, clipped to
+    /// the display. This is the inverse of [`Rect::to_landscape`].
+    pub fn from_landscape(&self) -> Option<Rect> {
+        let visible = self.intersection(&Rect::landscape())?;
+        Some(Rect::new(
+            visible.y,
+            EPD_WIDTH - visible.right(),
+            visible.height,
+            visible.width,
+        ))
+    }
+}
+
+/// How many pixels the union of two rectangles covers in addition to them.
+pub(crate) fn merge_waste(a: &Rect, b: &Rect) -> u64 {
+    a.union(b).area().saturating_sub(a.area() + b.area())
+}
+
+/// Merge rectangles until none of them overlap, also merging those whose
+/// union covers no more pixels than both of them.
+pub(crate) fn coalesce(rects: &mut Vec<Rect>) {
+    // Merging can make rectangles overlap others, so repeat until stable
+    let mut merged = true;
+    while merged {
+        merged = false;
+        'outer: for i in 0..rects.len() {
+            for j in i + 1..rects.len() {
+                if rects[i].intersection(&rects[j]).is_some()
+                    || merge_waste(&rects[i], &rects[j]) == 0
+                {
+                    rects[i] = rects[i].union(&rects[j]);
+                    rects.swap_remove(j);
+                    merged = true;
+                    break 'outer;
+                }
+            }
+        }
+    }
+}
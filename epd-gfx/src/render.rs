@@ -0,0 +1,162 @@
+//! Reading pixels back from a framebuffer, e.g. to show it in a preview
+//! window or to compare it in tests.
+
+use crate::{from_landscape, Framebuffer, Gray4, EPD_HEIGHT, EPD_WIDTH};
+
+/// The orientation of rendered images, named like epdiy's `EpdRotation`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// As the pixels are stored in the framebuffer, 960 pixels wide.
+    Landscape,
+    /// As the drawing functions see it, 540 pixels wide.
+    Portrait,
+    /// Landscape, turned upside down.
+    InvertedLandscape,
+    /// Portrait, turned upside down.
+    InvertedPortrait,
+}
+
+impl Rotation {
+    /// Width and height of an image rendered with this rotation.
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            Rotation::Landscape | Rotation::InvertedLandscape => (EPD_WIDTH, EPD_HEIGHT),
+            Rotation::Portrait | Rotation::InvertedPortrait => (EPD_HEIGHT, EPD_WIDTH),
+        }
+    }
+
+    /// Map a point of an image rendered with this rotation to the
+    /// portrait coordinates used for drawing.
+    /// ```
+    /// use epd_gfx::render::Rotation;
+    /// assert_eq!(Rotation::Portrait.to_portrait(5, 7), Some((5, 7)));
+    /// assert_eq!(Rotation::Landscape.to_portrait(959, 0), Some((0, 0)));
+    /// assert_eq!(Rotation::InvertedPortrait.to_portrait(539, 959), Some((0, 0)));
+    /// ```
+    pub fn to_portrait(&self, x: u32, y: u32) -> Option<(u32, u32)> {
+        let (w, h) = self.size();
+        if x >= w || y >= h {
+            return None;
+        }
+        match self {
+            Rotation::Landscape => from_landscape(x, y),
+            Rotation::InvertedLandscape => from_landscape(w - x - 1, h - y - 1),
+            Rotation::Portrait => Some((x, y)),
+            Rotation::InvertedPortrait => Some((w - x - 1, h - y - 1)),
+        }
+    }
+}
+
+/// Iterator over the colors of a rectangle of pixels, row by row.
+///
+/// The rectangle is clipped to the framebuffer.
+#[derive(Debug)]
+pub struct Pixels<'a, F: ?Sized> {
+    fb: &'a F,
+    x0: u32,
+    x1: u32,
+    y1: u32,
+    x: u32,
+    y: u32,
+}
+
+impl<F: Framebuffer + ?Sized> Iterator for Pixels<'_, F> {
+    type Item = Gray4;
+
+    fn next(&mut self) -> Option<Gray4> {
+        if self.x >= self.x1 {
+            self.x = self.x0;
+            self.y += 1;
+        }
+        if self.y >= self.y1 || self.x0 >= self.x1 {
+            return None;
+        }
+        let color = self.fb.get_pixel(self.x, self.y);
+        self.x += 1;
+        color
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let width = (self.x1 - self.x0) as usize;
+        let remaining = if self.y >= self.y1 || width == 0 {
+            0
+        } else {
+            (self.y1 - self.y) as usize * width - (self.x - self.x0) as usize
+        };
+        (remaining, Some(remaining))
+    }
+}
+
+/// Iterate over the colors in a rectangle, row by row.
+/// ```
+/// use epd_gfx::Gray4;
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// epd_gfx::fill_rect(&mut fb, 1, 1, 2, 2, Gray4::BLACK);
+/// let colors: Vec<Gray4> = epd_gfx::render::pixels(&fb, 0, 0, 3, 2).collect();
+/// let (w, b) = (Gray4::WHITE, Gray4::BLACK);
+/// assert_eq!(colors, [w, w, w, w, b, b]);
+/// ```
+pub fn pixels<F: Framebuffer + ?Sized>(fb: &F, x: u32, y: u32, w: u32, h: u32) -> Pixels<'_, F> {
+    let x0 = x.min(fb.width());
+    let y0 = y.min(fb.height());
+    Pixels {
+        fb,
+        x0,
+        x1: x.saturating_add(w).min(fb.width()),
+        y1: y.saturating_add(h).min(fb.height()),
+        x: x0,
+        y: y0,
+    }
+}
+
+/// Iterate over the colors in row `y`.
+pub fn row<F: Framebuffer + ?Sized>(fb: &F, y: u32) -> Pixels<'_, F> {
+    pixels(fb, 0, y, fb.width(), 1)
+}
+
+fn render<F: Framebuffer + ?Sized>(
+    fb: &F,
+    rotation: Rotation,
+    out: &mut [u8],
+    bytes_per_pixel: usize,
+    convert: impl Fn(u8, &mut [u8]),
+) {
+    let (width, height) = rotation.size();
+    assert_eq!(
+        out.len(),
+        (width * height) as usize * bytes_per_pixel,
+        "invalid output buffer size"
+    );
+    for (i, pixel) in out.chunks_exact_mut(bytes_per_pixel).enumerate() {
+        let (x, y) = (i as u32 % width, i as u32 / width);
+        let color = rotation
+            .to_portrait(x, y)
+            .and_then(|(x, y)| fb.get_pixel(x, y))
+            .unwrap_or(Gray4::WHITE);
+        convert(color.to_luma(), pixel);
+    }
+}
+
+/// Render the framebuffer into an RGBA8 image with the given rotation.
+///
+/// `out` must hold `4 * width * height` bytes, see [`Rotation::size`].
+pub fn render_rgba8<F: Framebuffer + ?Sized>(fb: &F, rotation: Rotation, out: &mut [u8]) {
+    render(fb, rotation, out, 4, |v, pixel| {
+        pixel.copy_from_slice(&[v, v, v, 0xFF])
+    });
+}
+
+/// Render the framebuffer into an 8 bit grayscale image with the given
+/// rotation.
+/// ```
+/// use epd_gfx::render::{render_gray8, Rotation};
+/// use epd_gfx::Gray4;
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// epd_gfx::draw_pixel(&mut fb, 0, 0, Gray4::BLACK);
+/// let mut out = vec![0; 960 * 540];
+/// render_gray8(&fb, Rotation::Landscape, &mut out);
+/// assert_eq!((out[959], out[958]), (0x00, 0xFF));
+/// ```
+pub fn render_gray8<F: Framebuffer + ?Sized>(fb: &F, rotation: Rotation, out: &mut [u8]) {
+    render(fb, rotation, out, 1, |v, pixel| pixel[0] = v);
+}
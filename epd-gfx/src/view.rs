@@ -0,0 +1,157 @@
+//! Restricting drawing to parts of a framebuffer.
+//!
+//! A [`View`] borrows a framebuffer and translates coordinates, so that a UI
+//! component can draw at (0, 0) into its own area. Everything drawn outside
+//! of the view or its current clip rectangle is discarded.
+
+use crate::rect::Rect;
+use crate::{Framebuffer, Gray4};
+
+/// A borrowed, clipped region of a framebuffer.
+/// ```
+/// use epd_gfx::rect::Rect;
+/// use epd_gfx::view::View;
+/// use epd_gfx::Gray4;
+///
+/// let mut fb = vec![0xFF; 960 * 540 / 2];
+/// let mut view = View::new(&mut fb);
+/// let mut button = view.sub_view(Rect::new(100, 200, 50, 20));
+/// epd_gfx::fill_rect(&mut button, 40, 0, 100, 100, Gray4::BLACK);
+/// assert_eq!(epd_gfx::get_pixel(&fb, 149, 219), Some(Gray4::BLACK));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 150, 219), Some(Gray4::WHITE));
+/// assert_eq!(epd_gfx::get_pixel(&fb, 149, 220), Some(Gray4::WHITE));
+/// ```
+#[derive(Debug)]
+pub struct View<'a, F: ?Sized> {
+    fb: &'a mut F,
+    /// Position of the view in the coordinates of `fb`.
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    /// Clip rectangles in view coordinates, each contained in the one below.
+    /// The first one is the area of the view which lies on `fb`.
+    clips: Vec<Rect>,
+}
+
+impl<'a, F: Framebuffer + ?Sized> View<'a, F> {
+    /// A view of the whole framebuffer.
+    pub fn new(fb: &'a mut F) -> Self {
+        let (width, height) = (fb.width(), fb.height());
+        Self {
+            fb,
+            x: 0,
+            y: 0,
+            width,
+            height,
+            clips: vec![Rect::new(0, 0, width, height)],
+        }
+    }
+
+    /// A view of `area`, given in the coordinates of this view.
+    ///
+    /// The sub-view starts out clipped to the current clip rectangle of this
+    /// view, so it can never draw outside of it.
+    /// ```
+    /// use epd_gfx::rect::Rect;
+    /// use epd_gfx::view::View;
+    /// use epd_gfx::Gray4;
+    ///
+    /// let mut fb = vec![0xFF; 960 * 540 / 2];
+    /// let mut view = View::new(&mut fb);
+    /// let mut panel = view.sub_view(Rect::new(10, 10, 100, 100));
+    /// let mut label = panel.sub_view(Rect::new(90, 5, 30, 10));
+    /// assert_eq!(label.clip(), Rect::new(0, 0, 10, 10));
+    /// epd_gfx::draw_pixel(&mut label, 0, 0, Gray4::BLACK);
+    /// epd_gfx::draw_pixel(&mut label, 10, 0, Gray4::BLACK);
+    /// assert_eq!(epd_gfx::get_pixel(&fb, 100, 15), Some(Gray4::BLACK));
+    /// assert_eq!(epd_gfx::get_pixel(&fb, 110, 15), Some(Gray4::WHITE));
+    /// ```
+    pub fn sub_view(&mut self, area: Rect) -> View<'_, F> {
+        let clip = self.clip();
+        let visible = area
+            .intersection(&clip)
+            .map(|r| Rect::new(r.x - area.x, r.y - area.y, r.width, r.height))
+            .unwrap_or_default();
+        View {
+            fb: &mut *self.fb,
+            x: self.x.saturating_add(area.x),
+            y: self.y.saturating_add(area.y),
+            width: area.width,
+            height: area.height,
+            clips: vec![visible],
+        }
+    }
+
+    /// The area in which drawing currently has an effect, in view
+    /// coordinates. It is empty if nothing of the view is visible.
+    pub fn clip(&self) -> Rect {
+        // There is always at least the clip rectangle of the view itself
+        *self.clips.last().unwrap()
+    }
+
+    /// Restrict drawing to `area` in addition to the current clip rectangle.
+    /// ```
+    /// use epd_gfx::rect::Rect;
+    /// use epd_gfx::view::View;
+    ///
+    /// let mut fb = vec![0xFF; 960 * 540 / 2];
+    /// let mut view = View::new(&mut fb);
+    /// view.push_clip(Rect::new(0, 0, 10, 10));
+    /// view.push_clip(Rect::new(5, 5, 10, 10));
+    /// assert_eq!(view.clip(), Rect::new(5, 5, 5, 5));
+    /// view.pop_clip();
+    /// assert_eq!(view.clip(), Rect::new(0, 0, 10, 10));
+    /// ```
+    pub fn push_clip(&mut self, area: Rect) {
+        let clip = self.clip().intersection(&area).unwrap_or_default();
+        self.clips.push(clip);
+    }
+
+    /// Remove the clip rectangle added last by [`View::push_clip`] and
+    /// return it. The area of the view itself can not be removed.
+    pub fn pop_clip(&mut self) -> Option<Rect> {
+        if self.clips.len() > 1 {
+            self.clips.pop()
+        } else {
+            None
+        }
+    }
+
+    /// Position and size of the view in the coordinates of the underlying
+    /// framebuffer.
+    pub fn area(&self) -> Rect {
+        Rect::new(self.x, self.y, self.width, self.height)
+    }
+}
+
+impl<F: Framebuffer + ?Sized> Framebuffer for View<'_, F> {
+    fn draw_pixel(&mut self, x: u32, y: u32, color: Gray4) {
+        if self.clip().contains(x, y) {
+            self.fb.draw_pixel(self.x + x, self.y + y, color);
+        }
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Option<Gray4> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.fb
+            .get_pixel(self.x.checked_add(x)?, self.y.checked_add(y)?)
+    }
+
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Gray4) {
+        if let Some(r) = Rect::new(x, y, w, h).intersection(&self.clip()) {
+            self.fb
+                .fill_rect(self.x + r.x, self.y + r.y, r.width, r.height, color);
+        }
+    }
+}
@@ -2,6 +2,10 @@ use std::env;
 use std::path::{Path, PathBuf};
 
 fn main() {
+    // The bindings are only used on the device, host builds use the simulator
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("espidf") {
+        return;
+    }
     let target = env::var("TARGET").unwrap();
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
 
@@ -35,7 +39,7 @@ fn run_bindgen(target: &str, header: &str, out: &Path) {
     let bindings = builder.generate().expect("Couldn't generate bindings!");
 
     bindings
-        .write_to_file(&out)
+        .write_to_file(out)
         .expect("Couldn't save bindings!");
 
     println!("cargo:rerun-if-changed={}", header);
@@ -0,0 +1,88 @@
+//! The display driver behind [`crate::epd::Epd`].
+//!
+//! On the device, `epdiy::Epdiy` drives the display through epdiy.
+//! [`crate::simulator::Simulator`] keeps the display in memory instead, so
+//! that application code and `Epd` itself can be run and tested on the host.
+
+use std::fmt;
+
+use epd_gfx::rect::Rect;
+use epd_gfx::Framebuffer;
+
+use crate::draw_mode::DrawMode;
+use crate::error::DrawError;
+use crate::init_options::InitOptions;
+use crate::temperature::TemperatureRange;
+
+/// The rotation of the drivers during updates. Areas are passed to
+/// [`EpdBackend::update_area`] in landscape coordinates already, and epdiy
+/// rotates them by its current rotation, so this must stay landscape.
+pub(crate) const UPDATE_ROTATION: Rotation = Rotation::Landscape;
+
+/// How coordinates are mapped onto the panel, like epdiy's `epd_set_rotation`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// The coordinates of the panel, 960x540.
+    Landscape,
+    /// The portrait coordinates of epd-gfx, 540x960.
+    Portrait,
+}
+
+impl Rotation {
+    /// The part of the panel covered by `area` in this rotation.
+    pub fn panel_area(self, area: Rect) -> Option<Rect> {
+        match self {
+            Rotation::Landscape => area.intersection(&Rect::landscape()),
+            Rotation::Portrait => area.to_landscape(),
+        }
+    }
+}
+
+/// The operations `Epd` needs from a display driver. `Epd` calls them in a
+/// valid order, e.g. updates only while the power is on.
+pub trait EpdBackend {
+    /// The 4bpp framebuffer in the landscape layout of the panel.
+    type Framebuffer: Framebuffer + fmt::Debug;
+
+    /// Initialize the driver. Called again after [`EpdBackend::deinit`].
+    fn init(&mut self, options: &InitOptions);
+
+    /// The framebuffer to draw on. Called once, after the first
+    /// [`EpdBackend::init`], and kept when the driver is shut down.
+    fn framebuffer(&mut self) -> Self::Framebuffer;
+
+    /// Shut down the driver.
+    fn deinit(&mut self);
+
+    fn power_on(&mut self);
+
+    fn power_off(&mut self);
+
+    /// Clear the display by flashing it. Requires power.
+    fn clear(&mut self);
+
+    /// The temperature of the board's sensor in degrees Celsius. Requires
+    /// power.
+    fn ambient_temperature(&mut self) -> f32;
+
+    /// The temperature ranges of the waveform in use.
+    fn temperature_ranges(&self) -> Vec<TemperatureRange>;
+
+    /// Show `fb` on the whole display. Requires power.
+    fn update_screen(
+        &mut self,
+        fb: &Self::Framebuffer,
+        mode: DrawMode,
+        temperature: i32,
+    ) -> Result<(), DrawError>;
+
+    /// Show `area` of `fb`, in landscape coordinates, on the display.
+    /// Requires power.
+    fn update_area(
+        &mut self,
+        fb: &Self::Framebuffer,
+        area: Rect,
+        mode: DrawMode,
+        temperature: i32,
+    ) -> Result<(), DrawError>;
+}
@@ -0,0 +1,361 @@
+//! epdiy's drawing functions, for the framebuffer of [`crate::epd::Epd`].
+//!
+//! These draw in the same portrait coordinates as epd-gfx and are faster on
+//! the device, but only work on the display's framebuffer. Pixels outside of
+//! the display are skipped. epdiy truncates coordinates to 16 bits, which
+//! would wrap far away shapes onto the display, so shapes have to lie within
+//! the range of `i16`.
+//! ```ignore
+//! let fb = epd.framebuffer_mut();
+//! draw::fill_circle(fb, (270, 480), 100, Gray4::BLACK)?;
+//! draw::draw_line(fb, (0, 0), (539, 959), Gray4::GRAY)?;
+//! ```
+
+use std::fmt;
+
+use epd_gfx::dirty::DirtyTracker;
+use epd_gfx::rect::Rect;
+use epd_gfx::Gray4;
+
+use crate::backend::Rotation;
+use crate::epd_highlevel::{self, EpdRect};
+use crate::epdiy::{with_rotation, FrontBuffer};
+
+/// Reasons why a shape or image can not be drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidShape {
+    /// A coordinate of the shape does not fit in 16 bits.
+    OutOfRange(i64),
+    /// The image data is shorter than the area requires.
+    ImageTooShort { len: usize, expected: usize },
+}
+
+impl fmt::Display for InvalidShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidShape::OutOfRange(coordinate) => {
+                write!(f, "coordinate {coordinate} is out of range")
+            }
+            InvalidShape::ImageTooShort { len, expected } => {
+                write!(f, "image has {len} bytes, but its area needs {expected}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidShape {}
+
+/// Draw a horizontal line of `length` pixels starting at (x, y).
+pub fn draw_hline(
+    fb: &mut DirtyTracker<FrontBuffer>,
+    x: i32,
+    y: i32,
+    length: u32,
+    color: Gray4,
+) -> Result<(), InvalidShape> {
+    if length == 0 {
+        return Ok(());
+    }
+    let ptr = touch(fb, Bounds::new(x, y, length, 1))?;
+    with_rotation(Rotation::Portrait, || unsafe {
+        epd_highlevel::epd_draw_hline(x, y, length as i32, epd_color(color), ptr)
+    });
+    Ok(())
+}
+
+/// Draw a vertical line of `length` pixels starting at (x, y).
+pub fn draw_vline(
+    fb: &mut DirtyTracker<FrontBuffer>,
+    x: i32,
+    y: i32,
+    length: u32,
+    color: Gray4,
+) -> Result<(), InvalidShape> {
+    if length == 0 {
+        return Ok(());
+    }
+    let ptr = touch(fb, Bounds::new(x, y, 1, length))?;
+    with_rotation(Rotation::Portrait, || unsafe {
+        epd_highlevel::epd_draw_vline(x, y, length as i32, epd_color(color), ptr)
+    });
+    Ok(())
+}
+
+/// Draw a line from `from` to `to`, both included.
+pub fn draw_line(
+    fb: &mut DirtyTracker<FrontBuffer>,
+    from: (i32, i32),
+    to: (i32, i32),
+    color: Gray4,
+) -> Result<(), InvalidShape> {
+    let ptr = touch(fb, Bounds::of(&[from, to]))?;
+    with_rotation(Rotation::Portrait, || unsafe {
+        epd_highlevel::epd_draw_line(from.0, from.1, to.0, to.1, epd_color(color), ptr)
+    });
+    Ok(())
+}
+
+/// Draw the outline of `rect`.
+pub fn draw_rect(
+    fb: &mut DirtyTracker<FrontBuffer>,
+    rect: Rect,
+    color: Gray4,
+) -> Result<(), InvalidShape> {
+    if rect.is_empty() {
+        return Ok(());
+    }
+    let ptr = touch(fb, Bounds::of_rect(rect))?;
+    with_rotation(Rotation::Portrait, || unsafe {
+        epd_highlevel::epd_draw_rect(epd_rect(rect), epd_color(color), ptr)
+    });
+    Ok(())
+}
+
+/// Fill `rect`.
+pub fn fill_rect(
+    fb: &mut DirtyTracker<FrontBuffer>,
+    rect: Rect,
+    color: Gray4,
+) -> Result<(), InvalidShape> {
+    // Only the visible part, to not loop over pixels which are skipped
+    let Some(rect) = rect.intersection(&Rect::portrait()) else {
+        return Ok(());
+    };
+    let ptr = touch(fb, Bounds::of_rect(rect))?;
+    with_rotation(Rotation::Portrait, || unsafe {
+        epd_highlevel::epd_fill_rect(epd_rect(rect), epd_color(color), ptr)
+    });
+    Ok(())
+}
+
+/// Draw the outline of a circle around `center`.
+pub fn draw_circle(
+    fb: &mut DirtyTracker<FrontBuffer>,
+    center: (i32, i32),
+    radius: u32,
+    color: Gray4,
+) -> Result<(), InvalidShape> {
+    let ptr = touch(fb, Bounds::of(&[center]).grow(radius))?;
+    let (x, y) = center;
+    with_rotation(Rotation::Portrait, || unsafe {
+        epd_highlevel::epd_draw_circle(x, y, radius as i32, epd_color(color), ptr)
+    });
+    Ok(())
+}
+
+/// Fill a circle around `center`.
+pub fn fill_circle(
+    fb: &mut DirtyTracker<FrontBuffer>,
+    center: (i32, i32),
+    radius: u32,
+    color: Gray4,
+) -> Result<(), InvalidShape> {
+    let ptr = touch(fb, Bounds::of(&[center]).grow(radius))?;
+    let (x, y) = center;
+    with_rotation(Rotation::Portrait, || unsafe {
+        epd_highlevel::epd_fill_circle(x, y, radius as i32, epd_color(color), ptr)
+    });
+    Ok(())
+}
+
+/// Draw the outline of the triangle between `a`, `b` and `c`.
+pub fn draw_triangle(
+    fb: &mut DirtyTracker<FrontBuffer>,
+    a: (i32, i32),
+    b: (i32, i32),
+    c: (i32, i32),
+    color: Gray4,
+) -> Result<(), InvalidShape> {
+    let ptr = touch(fb, Bounds::of(&[a, b, c]))?;
+    let color = epd_color(color);
+    with_rotation(Rotation::Portrait, || unsafe {
+        epd_highlevel::epd_draw_triangle(a.0, a.1, b.0, b.1, c.0, c.1, color, ptr)
+    });
+    Ok(())
+}
+
+/// Fill the triangle between `a`, `b` and `c`.
+pub fn fill_triangle(
+    fb: &mut DirtyTracker<FrontBuffer>,
+    a: (i32, i32),
+    b: (i32, i32),
+    c: (i32, i32),
+    color: Gray4,
+) -> Result<(), InvalidShape> {
+    let ptr = touch(fb, Bounds::of(&[a, b, c]))?;
+    let color = epd_color(color);
+    with_rotation(Rotation::Portrait, || unsafe {
+        epd_highlevel::epd_fill_triangle(a.0, a.1, b.0, b.1, c.0, c.1, color, ptr)
+    });
+    Ok(())
+}
+
+/// Draw a 4bpp image into `area`, whose size must match the image.
+///
+/// `data` holds two pixels per byte with the left one in the low nibble,
+/// like the framebuffer. Rows of odd width are padded to whole bytes.
+pub fn draw_image(
+    fb: &mut DirtyTracker<FrontBuffer>,
+    area: Rect,
+    data: &[u8],
+) -> Result<(), InvalidShape> {
+    if area.is_empty() {
+        return Ok(());
+    }
+    check_image(area, data)?;
+    let ptr = touch(fb, Bounds::of_rect(area))?;
+    with_rotation(Rotation::Portrait, || unsafe {
+        epd_highlevel::epd_draw_rotated_image(epd_rect(area), data.as_ptr(), ptr)
+    });
+    Ok(())
+}
+
+/// Draw an image like [`draw_image`], but skip its pixels of the
+/// `transparent` color.
+pub fn draw_image_transparent(
+    fb: &mut DirtyTracker<FrontBuffer>,
+    area: Rect,
+    data: &[u8],
+    transparent: Gray4,
+) -> Result<(), InvalidShape> {
+    if area.is_empty() {
+        return Ok(());
+    }
+    check_image(area, data)?;
+    let ptr = touch(fb, Bounds::of_rect(area))?;
+    let area = epd_rect(area);
+    let transparent = epd_color(transparent);
+    with_rotation(Rotation::Portrait, || unsafe {
+        epd_highlevel::epd_draw_rotated_transparent_image(area, data.as_ptr(), ptr, transparent)
+    });
+    Ok(())
+}
+
+/// Copy a 4bpp image in the layout of [`draw_image`] into `area`, given in
+/// landscape coordinates of the panel like the framebuffer itself.
+///
+/// This is the fastest way to draw an image, as the pixels are not rotated.
+pub fn copy_to_framebuffer(
+    fb: &mut DirtyTracker<FrontBuffer>,
+    area: Rect,
+    data: &[u8],
+) -> Result<(), InvalidShape> {
+    if area.is_empty() {
+        return Ok(());
+    }
+    check_image(area, data)?;
+    Bounds::of_rect(area).check()?;
+    if let Some(area) = area.from_landscape() {
+        fb.mark_dirty(area);
+    }
+    let ptr = fb.inner_mut().as_bytes_mut().as_mut_ptr();
+    unsafe { epd_highlevel::epd_copy_to_framebuffer(epd_rect(area), data.as_ptr(), ptr) };
+    Ok(())
+}
+
+/// The smallest rectangle containing a shape, with inclusive maximums.
+struct Bounds {
+    min: (i64, i64),
+    max: (i64, i64),
+}
+
+impl Bounds {
+    fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        let (x, y) = (i64::from(x), i64::from(y));
+        Bounds {
+            min: (x, y),
+            max: (x + i64::from(width) - 1, y + i64::from(height) - 1),
+        }
+    }
+
+    fn of_rect(rect: Rect) -> Self {
+        let (x, y) = (i64::from(rect.x), i64::from(rect.y));
+        Bounds {
+            min: (x, y),
+            max: (
+                x + i64::from(rect.width) - 1,
+                y + i64::from(rect.height) - 1,
+            ),
+        }
+    }
+
+    fn of(points: &[(i32, i32)]) -> Self {
+        let xs = points.iter().map(|&(x, _)| i64::from(x));
+        let ys = points.iter().map(|&(_, y)| i64::from(y));
+        Bounds {
+            min: (xs.clone().min().unwrap_or(0), ys.clone().min().unwrap_or(0)),
+            max: (xs.max().unwrap_or(0), ys.max().unwrap_or(0)),
+        }
+    }
+
+    fn grow(self, by: u32) -> Self {
+        let by = i64::from(by);
+        Bounds {
+            min: (self.min.0 - by, self.min.1 - by),
+            max: (self.max.0 + by, self.max.1 + by),
+        }
+    }
+
+    fn check(&self) -> Result<(), InvalidShape> {
+        let range = i64::from(i16::MIN)..=i64::from(i16::MAX);
+        [self.min.0, self.min.1, self.max.0, self.max.1]
+            .into_iter()
+            .find(|coordinate| !range.contains(coordinate))
+            .map_or(Ok(()), |coordinate| {
+                Err(InvalidShape::OutOfRange(coordinate))
+            })
+    }
+
+    /// The part of the bounds on the display.
+    fn to_rect(&self) -> Option<Rect> {
+        let x = self.min.0.max(0);
+        let y = self.min.1.max(0);
+        if self.max.0 < x || self.max.1 < y {
+            return None;
+        }
+        let rect = Rect::new(
+            x as u32,
+            y as u32,
+            (self.max.0 - x + 1) as u32,
+            (self.max.1 - y + 1) as u32,
+        );
+        rect.intersection(&Rect::portrait())
+    }
+}
+
+/// Check `bounds` and record them as changed, returning the framebuffer to
+/// draw on.
+fn touch(fb: &mut DirtyTracker<FrontBuffer>, bounds: Bounds) -> Result<*mut u8, InvalidShape> {
+    bounds.check()?;
+    if let Some(area) = bounds.to_rect() {
+        fb.mark_dirty(area);
+    }
+    Ok(fb.inner_mut().as_bytes_mut().as_mut_ptr())
+}
+
+/// epdiy reads `height` rows of `width` pixels, padded to whole bytes.
+fn check_image(area: Rect, data: &[u8]) -> Result<(), InvalidShape> {
+    let row = (area.width as usize).div_ceil(2);
+    let expected = row.saturating_mul(area.height as usize);
+    if data.len() < expected {
+        return Err(InvalidShape::ImageTooShort {
+            len: data.len(),
+            expected,
+        });
+    }
+    Ok(())
+}
+
+/// epdiy takes colors as 8 bit values and uses the upper nibble.
+fn epd_color(color: Gray4) -> u8 {
+    color.level() << 4
+}
+
+fn epd_rect(rect: Rect) -> EpdRect {
+    EpdRect {
+        x: rect.x as i32,
+        y: rect.y as i32,
+        width: rect.width as i32,
+        height: rect.height as i32,
+    }
+}
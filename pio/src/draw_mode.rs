@@ -0,0 +1,294 @@
+//! Draw modes and flags of epdiy's `enum EpdDrawMode`.
+//!
+//! The C enum mixes the waveform to use (lower 6 bits) with the framebuffer
+//! packing and the assumed previous content of the display. [`DrawMode`]
+//! selects the waveform, [`DrawFlags::builder`] adds the flags and rejects
+//! combinations epdiy can not draw.
+
+use std::fmt;
+
+const PACKING_MASK: u32 = 0x40 | 0x80 | 0x100;
+const PREVIOUSLY_WHITE: u32 = 0x200;
+const PREVIOUSLY_BLACK: u32 = 0x400;
+
+/// The waveform used to update the display.
+///
+/// Modes marked as not available with the default epdiy waveforms need a
+/// vendor waveform, otherwise the update fails with `MODE_NOT_FOUND`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DrawMode {
+    /// Direct update: go from any color to black or white only. Fast and
+    /// without flashing.
+    Du,
+    /// Go from any gray level to another with a flashing update.
+    Gc16,
+    /// Faster version of [`DrawMode::Gc16`].
+    /// Not available with the default epdiy waveforms.
+    Gc16Fast,
+    /// Animation mode: fast, monochrome updates.
+    /// Not available with the default epdiy waveforms.
+    A2,
+    /// Go from any gray level to another without flashing.
+    Gl16,
+    /// Faster version of [`DrawMode::Gl16`].
+    /// Not available with the default epdiy waveforms.
+    Gl16Fast,
+    /// A 4 gray level version of [`DrawMode::Du`].
+    /// Not available with the default epdiy waveforms.
+    Du4,
+    /// Arbitrary transitions between 4 gray levels.
+    /// Not available with the default epdiy waveforms.
+    Gl4,
+    /// Not available with the default epdiy waveforms.
+    Gl16Inv,
+    /// Go from a white screen to any gray level, quickly.
+    /// Only available with epdiy waveforms.
+    WhiteToGl16,
+    /// Go from a black screen to any gray level, quickly.
+    /// Only available with epdiy waveforms.
+    BlackToGl16,
+    /// Monochrome drawing without a waveform, only for 1bpp framebuffers.
+    Monochrome,
+}
+
+impl DrawMode {
+    /// The value of the mode in `enum EpdDrawMode`.
+    pub const fn bits(self) -> u32 {
+        match self {
+            DrawMode::Du => 0x1,
+            DrawMode::Gc16 => 0x2,
+            DrawMode::Gc16Fast => 0x3,
+            DrawMode::A2 => 0x4,
+            DrawMode::Gl16 => 0x5,
+            DrawMode::Gl16Fast => 0x6,
+            DrawMode::Du4 => 0x7,
+            DrawMode::Gl4 => 0xA,
+            DrawMode::Gl16Inv => 0xB,
+            DrawMode::WhiteToGl16 => 0x10,
+            DrawMode::BlackToGl16 => 0x11,
+            DrawMode::Monochrome => 0x20,
+        }
+    }
+}
+
+/// How the pixels of the drawn framebuffer are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Packing {
+    /// 1 bit per pixel, 0 is black and 1 is white (`MODE_PACKING_8PPB`).
+    Bpp1,
+    /// 4 bits per pixel, the layout of the epd-gfx framebuffer
+    /// (`MODE_PACKING_2PPB`).
+    Bpp4,
+    /// One byte per pixel with the new color in the upper and the previous
+    /// color in the lower nibble (`MODE_PACKING_1PPB_DIFFERENCE`).
+    Difference,
+}
+
+impl Packing {
+    const fn bits(self) -> u32 {
+        match self {
+            Packing::Bpp1 => 0x40,
+            Packing::Bpp4 => 0x80,
+            Packing::Difference => 0x100,
+        }
+    }
+}
+
+/// Reasons why a combination of draw mode and flags can not be drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidDrawFlags {
+    /// The display can not have been white and black before.
+    ConflictingBackground,
+    /// [`DrawMode::Monochrome`] only works with [`Packing::Bpp1`] and the
+    /// other way around.
+    MonochromeNeedsBpp1,
+    /// epdiy only implements monochrome drawing on a white display.
+    MonochromeNeedsWhiteBackground,
+    /// A difference image already holds the previous colors.
+    DifferenceWithBackground,
+    /// The high level API chooses packing and background itself, so they can
+    /// not be given.
+    FlagsNotAllowed,
+}
+
+impl fmt::Display for InvalidDrawFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidDrawFlags::ConflictingBackground => {
+                write!(f, "previously white and previously black are exclusive")
+            }
+            InvalidDrawFlags::MonochromeNeedsBpp1 => {
+                write!(f, "monochrome mode requires 1bpp packing and vice versa")
+            }
+            InvalidDrawFlags::MonochromeNeedsWhiteBackground => {
+                write!(f, "monochrome mode requires a previously white display")
+            }
+            InvalidDrawFlags::DifferenceWithBackground => {
+                write!(f, "difference packing can not assume a previous color")
+            }
+            InvalidDrawFlags::FlagsNotAllowed => {
+                write!(f, "packing and background are chosen by the high level API")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidDrawFlags {}
+
+/// A draw mode together with its packing and background flags, checked to be
+/// a combination epdiy can draw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DrawFlags {
+    mode: DrawMode,
+    packing: Option<Packing>,
+    bits: u32,
+}
+
+impl DrawFlags {
+    /// Combine `mode` with flags, which are checked by
+    /// [`DrawFlagsBuilder::build`].
+    /// ```
+    /// use esp_idf_rs::draw_mode::{DrawFlags, DrawMode, Packing};
+    /// let flags = DrawFlags::builder(DrawMode::Gc16)
+    ///     .packing(Packing::Bpp4)
+    ///     .previously_white()
+    ///     .build()
+    ///     .unwrap();
+    /// // MODE_GC16 | MODE_PACKING_2PPB | PREVIOUSLY_WHITE
+    /// assert_eq!(flags.bits(), 0x2 | 0x80 | 0x200);
+    /// assert_eq!(flags.packing(), Some(Packing::Bpp4));
+    /// assert!(flags.has_flags());
+    ///
+    /// let flags = DrawFlags::from(DrawMode::Du);
+    /// assert_eq!(flags.bits(), 0x1);
+    /// assert!(!flags.has_flags());
+    /// ```
+    pub fn builder(mode: DrawMode) -> DrawFlagsBuilder {
+        DrawFlagsBuilder {
+            mode,
+            packing: None,
+            previously_white: false,
+            previously_black: false,
+        }
+    }
+
+    pub fn mode(&self) -> DrawMode {
+        self.mode
+    }
+
+    pub fn packing(&self) -> Option<Packing> {
+        self.packing
+    }
+
+    /// Whether packing or background flags are set. The high level API
+    /// chooses these itself for every update.
+    pub fn has_flags(&self) -> bool {
+        self.bits & (PACKING_MASK | PREVIOUSLY_WHITE | PREVIOUSLY_BLACK) != 0
+    }
+
+    /// The value to pass to epdiy as `enum EpdDrawMode`.
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+}
+
+impl From<DrawMode> for DrawFlags {
+    /// Only the waveform, as used by the high level API.
+    fn from(mode: DrawMode) -> Self {
+        Self {
+            mode,
+            packing: None,
+            bits: mode.bits(),
+        }
+    }
+}
+
+/// Builder for [`DrawFlags`], see [`DrawFlags::builder`].
+#[derive(Debug, Clone, Copy)]
+pub struct DrawFlagsBuilder {
+    mode: DrawMode,
+    packing: Option<Packing>,
+    previously_white: bool,
+    previously_black: bool,
+}
+
+impl DrawFlagsBuilder {
+    pub fn packing(mut self, packing: Packing) -> Self {
+        self.packing = Some(packing);
+        self
+    }
+
+    /// Assert that the display is white before the update
+    /// (`PREVIOUSLY_WHITE`). With [`Packing::Bpp4`] this allows a faster
+    /// lookup of the output.
+    pub fn previously_white(mut self) -> Self {
+        self.previously_white = true;
+        self
+    }
+
+    /// Assert that the display is black before the update
+    /// (`PREVIOUSLY_BLACK`).
+    pub fn previously_black(mut self) -> Self {
+        self.previously_black = true;
+        self
+    }
+
+    /// Check that epdiy can draw the combination.
+    /// ```
+    /// use esp_idf_rs::draw_mode::{DrawFlags, DrawMode, InvalidDrawFlags, Packing};
+    /// let both = DrawFlags::builder(DrawMode::Gl16)
+    ///     .previously_white()
+    ///     .previously_black();
+    /// assert_eq!(both.build(), Err(InvalidDrawFlags::ConflictingBackground));
+    ///
+    /// let mono = DrawFlags::builder(DrawMode::Monochrome);
+    /// assert_eq!(mono.build(), Err(InvalidDrawFlags::MonochromeNeedsBpp1));
+    /// let mono = mono.packing(Packing::Bpp1);
+    /// assert_eq!(
+    ///     mono.build(),
+    ///     Err(InvalidDrawFlags::MonochromeNeedsWhiteBackground)
+    /// );
+    /// // MODE_EPDIY_MONOCHROME | MODE_PACKING_8PPB | PREVIOUSLY_WHITE
+    /// assert_eq!(mono.previously_white().build().unwrap().bits(), 0x20 | 0x40 | 0x200);
+    ///
+    /// let bpp1 = DrawFlags::builder(DrawMode::Du).packing(Packing::Bpp1);
+    /// assert_eq!(bpp1.build(), Err(InvalidDrawFlags::MonochromeNeedsBpp1));
+    ///
+    /// let difference = DrawFlags::builder(DrawMode::Gc16).packing(Packing::Difference);
+    /// assert_eq!(
+    ///     difference.previously_black().build(),
+    ///     Err(InvalidDrawFlags::DifferenceWithBackground)
+    /// );
+    /// // MODE_GC16 | MODE_PACKING_1PPB_DIFFERENCE
+    /// assert_eq!(difference.build().unwrap().bits(), 0x2 | 0x100);
+    /// ```
+    pub fn build(self) -> Result<DrawFlags, InvalidDrawFlags> {
+        if self.previously_white && self.previously_black {
+            return Err(InvalidDrawFlags::ConflictingBackground);
+        }
+        let monochrome = self.mode == DrawMode::Monochrome;
+        if monochrome != (self.packing == Some(Packing::Bpp1)) {
+            return Err(InvalidDrawFlags::MonochromeNeedsBpp1);
+        }
+        if monochrome && !self.previously_white {
+            return Err(InvalidDrawFlags::MonochromeNeedsWhiteBackground);
+        }
+        let background = self.previously_white || self.previously_black;
+        if self.packing == Some(Packing::Difference) && background {
+            return Err(InvalidDrawFlags::DifferenceWithBackground);
+        }
+
+        let mut bits = self.mode.bits() | self.packing.map_or(0, Packing::bits);
+        if self.previously_white {
+            bits |= PREVIOUSLY_WHITE;
+        }
+        if self.previously_black {
+            bits |= PREVIOUSLY_BLACK;
+        }
+        Ok(DrawFlags {
+            mode: self.mode,
+            packing: self.packing,
+            bits,
+        })
+    }
+}
@@ -1,83 +1,427 @@
-use crate::epd_highlevel;
-use crate::epd_highlevel::EpdiyHighlevelState;
-use crate::firasans::FiraSans_12;
+use epd_gfx::dirty::DirtyTracker;
+use epd_gfx::rect::Rect;
 
-const EPD_WIDTH: usize = 960;
-const EPD_HEIGHT: usize = 540;
-const FB_SIZE: usize = EPD_WIDTH / 2 * EPD_HEIGHT;
+use crate::backend::EpdBackend;
+use crate::draw_mode::{DrawFlags, DrawMode, InvalidDrawFlags};
+use crate::error::EpdError;
+use crate::init_options::InitOptions;
+use crate::temperature::{Temperature, TemperatureReading};
+#[cfg(target_os = "espidf")]
+use crate::{
+    epd_highlevel::EpdFont,
+    epdiy::Epdiy,
+    text::{self, Cursor, FontProperties},
+};
 
+/// The driver has not been initialized yet.
 #[derive(Debug)]
-enum EpdState {
-    Uninitialized,
-    HighlevelState(EpdiyHighlevelState),
+pub struct Uninitialized<B> {
+    backend: B,
 }
 
+/// The driver is initialized and the display can be updated.
 #[derive(Debug)]
-pub struct Epd {
-    epd_state: EpdState,
+pub struct Initialized<B: EpdBackend> {
+    backend: B,
+    fb: DirtyTracker<B::Framebuffer>,
+    temperature: Temperature,
 }
 
-impl<'a> Epd {
-    pub fn new() -> Self {
+/// The driver was shut down with [`Epd::deinit`]. The framebuffer keeps its
+/// contents, so the display can be initialized again, e.g. after sleep.
+#[derive(Debug)]
+pub struct Deinitialized<B: EpdBackend> {
+    backend: B,
+    fb: B::Framebuffer,
+}
+
+/// The display, in one of the states [`Uninitialized`], [`Initialized`] or
+/// [`Deinitialized`]. Methods are only available in the state in which they
+/// can be used.
+/// ```
+/// use epd_gfx::{Framebuffer, Gray4};
+/// use esp_idf_rs::draw_mode::DrawMode;
+/// use esp_idf_rs::epd::Epd;
+/// use esp_idf_rs::init_options::InitOptions;
+/// use esp_idf_rs::simulator::Simulator;
+///
+/// let mut epd = Epd::new(Simulator::new()).init(InitOptions::new());
+/// epd.framebuffer_mut().draw_pixel(1, 2, Gray4::BLACK);
+///
+/// // E.g. around deep sleep, keeping the framebuffer
+/// let epd = epd.deinit();
+/// let mut epd = epd.init(InitOptions::new());
+/// assert_eq!(epd.framebuffer().get_pixel(1, 2), Some(Gray4::BLACK));
+/// epd.update_screen(DrawMode::Gc16).unwrap();
+/// assert_eq!(epd.backend().pixel(1, 2), Some(Gray4::BLACK));
+/// ```
+/// The display can not be updated before it is initialized:
+/// ```compile_fail
+/// # use esp_idf_rs::{draw_mode::DrawMode, epd::Epd, simulator::Simulator};
+/// let mut epd = Epd::new(Simulator::new());
+/// epd.update_screen(DrawMode::Gc16);
+/// ```
+#[derive(Debug)]
+pub struct Epd<S> {
+    state: S,
+}
+
+#[cfg(target_os = "espidf")]
+impl Epd<Uninitialized<Epdiy>> {
+    /// The display of the board, or `None` if it was taken before.
+    pub fn take() -> Option<Self> {
+        Epdiy::take().map(Epd::new)
+    }
+}
+
+impl<B: EpdBackend> Epd<Uninitialized<B>> {
+    /// The display driven by `backend`, e.g. a
+    /// [`crate::simulator::Simulator`] on the host.
+    pub fn new(backend: B) -> Self {
         Self {
-            epd_state: EpdState::Uninitialized,
+            state: Uninitialized { backend },
         }
     }
 
-    pub fn init(&mut self) {
-        const EPD_LUT_4K: u32 = 2;
-        unsafe { epd_highlevel::epd_init(EPD_LUT_4K) };
-        let state: epd_highlevel::EpdiyHighlevelState = unsafe { epd_highlevel::epd_hl_init() };
-        self.epd_state = EpdState::HighlevelState(state);
+    /// Initialize the driver and allocate the framebuffers.
+    pub fn init(self, options: InitOptions) -> Epd<Initialized<B>> {
+        let mut backend = self.state.backend;
+        backend.init(&options);
+        let fb = backend.framebuffer();
+        Epd::initialized(backend, fb)
     }
+}
 
-    pub fn clear(&self) -> () {
-        match self.epd_state {
-            EpdState::Uninitialized => {}
-            EpdState::HighlevelState(_) => {
-                unsafe { epd_highlevel::epd_clear() };
-            }
+impl<B: EpdBackend> Epd<Deinitialized<B>> {
+    /// Initialize the driver again, keeping the framebuffer contents.
+    pub fn init(self, options: InitOptions) -> Epd<Initialized<B>> {
+        let Deinitialized { mut backend, fb } = self.state;
+        backend.init(&options);
+        Epd::initialized(backend, fb)
+    }
+}
+
+impl<B: EpdBackend> Epd<Initialized<B>> {
+    fn initialized(backend: B, fb: B::Framebuffer) -> Self {
+        Self {
+            state: Initialized {
+                backend,
+                fb: DirtyTracker::new(fb),
+                temperature: Temperature::default(),
+            },
         }
     }
 
-    /// Get a mutable slice into the  display framebuffer, if it is initialized.
-    pub fn get_framebuffer(&self) -> Option<&'a mut [u8]> {
-        match self.epd_state {
-            EpdState::Uninitialized => None,
-            EpdState::HighlevelState(state) => {
-                let fb: &mut [u8] =
-                    unsafe { std::slice::from_raw_parts_mut(state.front_fb, FB_SIZE) };
-                Some(fb)
-            }
+    /// The driver, e.g. to inspect a [`crate::simulator::Simulator`].
+    pub fn backend(&self) -> &B {
+        &self.state.backend
+    }
+
+    /// Clear the display, see [`PowerSession::clear`].
+    pub fn clear(&mut self) {
+        self.power_on().clear();
+    }
+
+    /// The 4bpp framebuffer to draw on, see `epd_gfx`. It is shown on the
+    /// display by the next update.
+    pub fn framebuffer(&self) -> &B::Framebuffer {
+        self.state.fb.inner()
+    }
+
+    /// The framebuffer, borrowed mutably until the next call on `self`.
+    ///
+    /// Changes made through it are tracked, so that [`Epd::update_dirty`]
+    /// only refreshes the areas that were drawn on.
+    pub fn framebuffer_mut(&mut self) -> &mut DirtyTracker<B::Framebuffer> {
+        &mut self.state.fb
+    }
+
+    /// Turn on the power of the display for several updates. It is turned
+    /// off again when the returned session is dropped.
+    /// ```
+    /// use esp_idf_rs::draw_mode::DrawMode;
+    /// use esp_idf_rs::epd::Epd;
+    /// use esp_idf_rs::init_options::InitOptions;
+    /// use esp_idf_rs::simulator::Simulator;
+    ///
+    /// let mut epd = Epd::new(Simulator::new()).init(InitOptions::new());
+    /// {
+    ///     let mut session = epd.power_on();
+    ///     session.update_screen(DrawMode::Gc16).unwrap();
+    ///     session.update_screen(DrawMode::A2).unwrap();
+    /// }
+    /// assert!(!epd.backend().is_powered());
+    ///
+    /// // Also when unwinding from a panic
+    /// let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+    ///     let _session = epd.power_on();
+    ///     panic!("drawing failed");
+    /// }));
+    /// assert!(result.is_err());
+    /// assert!(!epd.backend().is_powered());
+    /// ```
+    pub fn power_on(&mut self) -> PowerSession<'_, B> {
+        self.state.backend.power_on();
+        PowerSession { epd: self }
+    }
+
+    /// Update the screen to display the current contents of the framebuffer,
+    /// see [`PowerSession::update_screen`].
+    pub fn update_screen(&mut self, mode: impl Into<DrawFlags>) -> Result<(), EpdError> {
+        self.power_on().update_screen(mode)
+    }
+
+    /// Update only `area` of the screen, see [`PowerSession::update_area`].
+    pub fn update_area(&mut self, area: Rect, mode: impl Into<DrawFlags>) -> Result<(), EpdError> {
+        self.power_on().update_area(area, mode)
+    }
+
+    /// Update the areas of the screen changed since the last update, see
+    /// [`PowerSession::update_dirty`]. The display is only powered on if
+    /// something changed, but `mode` is checked either way.
+    /// ```
+    /// use esp_idf_rs::draw_mode::{DrawFlags, DrawMode, Packing};
+    /// use esp_idf_rs::epd::Epd;
+    /// use esp_idf_rs::init_options::InitOptions;
+    /// use esp_idf_rs::simulator::Simulator;
+    ///
+    /// let mut epd = Epd::new(Simulator::new()).init(InitOptions::new());
+    /// epd.update_dirty(DrawMode::Gc16).unwrap();
+    /// assert!(epd.backend().updates().is_empty());
+    ///
+    /// let flags = DrawFlags::builder(DrawMode::Gc16)
+    ///     .packing(Packing::Bpp4)
+    ///     .build()
+    ///     .unwrap();
+    /// assert!(epd.update_dirty(flags).is_err());
+    /// ```
+    pub fn update_dirty(&mut self, mode: impl Into<DrawFlags>) -> Result<(), EpdError> {
+        let mode = mode.into();
+        highlevel_mode(mode)?;
+        if !self.state.fb.is_dirty() {
+            return Ok(());
         }
+        self.power_on().update_dirty(mode)
+    }
+
+    fn update_landscape_area(
+        &mut self,
+        area: Rect,
+        mode: DrawMode,
+        temperature: i32,
+    ) -> Result<(), EpdError> {
+        let state = &mut self.state;
+        let fb = state.fb.inner();
+        Ok(state.backend.update_area(fb, area, mode, temperature)?)
+    }
+
+    /// Where the temperature for updates comes from, e.g. to override it.
+    pub fn temperature_source(&mut self) -> &mut Temperature {
+        &mut self.state.temperature
     }
 
-    pub fn write_text(&mut self, x: usize, y: usize, text: String) {
-        let t = text.as_ptr() as *const i8;
-        let font = &FiraSans_12 as *const epd_highlevel::EpdFont;
-        let x_ptr = &x as *const usize as *mut i32;
-        let y_ptr = &y as *const usize as *mut i32;
+    /// The temperature used for the next update. The display is powered on
+    /// to read the sensor, see [`PowerSession::temperature`].
+    pub fn temperature(&mut self) -> TemperatureReading {
+        self.power_on().temperature()
+    }
 
-        if let Some(fb) = self.get_framebuffer() {
-            unsafe { epd_highlevel::epd_write_default(font, t, x_ptr, y_ptr, fb.as_mut_ptr()) };
+    /// Shut down the driver, e.g. before deep sleep.
+    pub fn deinit(self) -> Epd<Deinitialized<B>> {
+        let Initialized {
+            mut backend, fb, ..
+        } = self.state;
+        backend.deinit();
+        Epd {
+            state: Deinitialized {
+                backend,
+                fb: fb.into_inner(),
+            },
         }
     }
+}
+
+#[cfg(target_os = "espidf")]
+impl Epd<Initialized<Epdiy>> {
+    /// Write `text` with `font` at `cursor`, see [`crate::text`]. Returns the
+    /// cursor after the text, to continue writing there.
+    /// ```ignore
+    /// let props = FontProperties::new().alignment(Alignment::Center);
+    /// epd.write_text(&FiraSans_12, "Hello\nworld", Cursor::new(270, 480), &props)?;
+    /// ```
+    pub fn write_text(
+        &mut self,
+        font: &EpdFont,
+        text: &str,
+        cursor: Cursor,
+        props: &FontProperties,
+    ) -> Result<Cursor, EpdError> {
+        let bounds = text::text_bounds(font, text, cursor, props)?;
+        if let Some(area) = bounds.to_rect() {
+            self.state.fb.mark_dirty(area);
+        }
+        let fb = self.state.fb.inner_mut().as_bytes_mut();
+        Ok(text::write_text(fb, font, text, cursor, props)?)
+    }
+}
+
+/// Keeps the display powered while updating it several times in a row,
+/// see [`Epd::power_on`]. The power is turned off when the session is
+/// dropped, also when unwinding from a panic.
+#[derive(Debug)]
+pub struct PowerSession<'a, B: EpdBackend> {
+    epd: &'a mut Epd<Initialized<B>>,
+}
+
+impl<B: EpdBackend> PowerSession<'_, B> {
+    /// Clear the display by flashing it black and white a few times. The
+    /// framebuffer is not changed.
+    pub fn clear(&mut self) {
+        self.epd.state.backend.clear();
+    }
+
+    /// The framebuffer, to draw between updates.
+    pub fn framebuffer_mut(&mut self) -> &mut DirtyTracker<B::Framebuffer> {
+        self.epd.framebuffer_mut()
+    }
+
+    /// The temperature used for the next update.
+    pub fn temperature(&mut self) -> TemperatureReading {
+        let state = &mut self.epd.state;
+        state.temperature.read(&mut state.backend)
+    }
+
+    /// The temperature to pass to the driver, checked against the waveform.
+    fn update_temperature(&mut self) -> Result<i32, EpdError> {
+        let reading = self.temperature();
+        if let Some(e) = reading.out_of_range {
+            if self.epd.state.temperature.is_strict() {
+                return Err(e.into());
+            }
+        }
+        Ok(reading.celsius)
+    }
 
     /// Update the screen to display the current contents of the framebuffer.
-    pub fn update_screen(&self, temperature: i32) -> () {
-        match self.epd_state {
-            EpdState::Uninitialized => {}
-            EpdState::HighlevelState(mut state) => {
-                unsafe { epd_highlevel::epd_poweron() };
-
-                const MODE_GC16: epd_highlevel::EpdDrawMode = 0x2;
-                let result: epd_highlevel::EpdDrawError = unsafe {
-                    epd_highlevel::epd_hl_update_screen(&mut state, MODE_GC16, temperature)
-                };
-                println!("Draw result: {result:?}");
-
-                unsafe { epd_highlevel::epd_poweroff() };
+    ///
+    /// The high level API picks the framebuffer packing and background
+    /// itself, so `mode` must not set any flags, e.g. `DrawMode::Gc16`.
+    pub fn update_screen(&mut self, mode: impl Into<DrawFlags>) -> Result<(), EpdError> {
+        let mode = highlevel_mode(mode.into())?;
+        let temperature = self.update_temperature()?;
+        let state = &mut self.epd.state;
+        let fb = state.fb.inner();
+        state.backend.update_screen(fb, mode, temperature)?;
+        state.fb.reset_dirty();
+        Ok(())
+    }
+
+    /// Update only `area` of the screen, given in the portrait coordinates
+    /// used for drawing. The changes to the framebuffer are only forgotten if
+    /// they all lie within `area`.
+    /// ```
+    /// use epd_gfx::rect::Rect;
+    /// use epd_gfx::Gray4;
+    /// use esp_idf_rs::draw_mode::DrawMode;
+    /// use esp_idf_rs::epd::Epd;
+    /// use esp_idf_rs::init_options::InitOptions;
+    /// use esp_idf_rs::simulator::Simulator;
+    ///
+    /// let mut epd = Epd::new(Simulator::new()).init(InitOptions::new());
+    /// let mut session = epd.power_on();
+    /// epd_gfx::fill_rect(session.framebuffer_mut(), 10, 10, 20, 20, Gray4::BLACK);
+    ///
+    /// session.update_area(Rect::new(0, 0, 20, 20), DrawMode::Gc16).unwrap();
+    /// assert!(session.framebuffer_mut().is_dirty());
+    /// session.update_area(Rect::new(0, 0, 100, 100), DrawMode::Gc16).unwrap();
+    /// assert!(!session.framebuffer_mut().is_dirty());
+    /// drop(session);
+    ///
+    /// let display = epd.backend();
+    /// assert_eq!(display.pixel(25, 25), Some(Gray4::BLACK));
+    /// assert_eq!(display.pixel(35, 35), Some(Gray4::WHITE));
+    /// ```
+    pub fn update_area(&mut self, area: Rect, mode: impl Into<DrawFlags>) -> Result<(), EpdError> {
+        let mode = highlevel_mode(mode.into())?;
+        let Some(area) = area.to_landscape() else {
+            return Ok(());
+        };
+        let temperature = self.update_temperature()?;
+        let result = self.epd.update_landscape_area(area, mode, temperature);
+
+        // Forget the changes if they were all inside of the updated area
+        if let Some(bounds) = self.epd.state.fb.dirty_bounds() {
+            if result.is_ok() && bounds.intersection(&area) == Some(bounds) {
+                self.epd.state.fb.reset_dirty();
             }
         }
+        result
+    }
+
+    /// Update the areas of the screen changed through
+    /// [`PowerSession::framebuffer_mut`] since the last update.
+    /// ```
+    /// use epd_gfx::Gray4;
+    /// use esp_idf_rs::draw_mode::DrawMode;
+    /// use esp_idf_rs::epd::Epd;
+    /// use esp_idf_rs::init_options::InitOptions;
+    /// use esp_idf_rs::simulator::Simulator;
+    ///
+    /// let mut epd = Epd::new(Simulator::new()).init(InitOptions::new());
+    /// let mut session = epd.power_on();
+    /// epd_gfx::fill_rect(session.framebuffer_mut(), 0, 0, 10, 10, Gray4::BLACK);
+    /// epd_gfx::fill_rect(session.framebuffer_mut(), 500, 900, 10, 10, Gray4::GRAY);
+    /// session.update_dirty(DrawMode::Gc16).unwrap();
+    /// assert!(!session.framebuffer_mut().is_dirty());
+    ///
+    /// // Nothing changed since
+    /// session.update_dirty(DrawMode::Gc16).unwrap();
+    /// drop(session);
+    /// assert_eq!(epd.backend().updates().len(), 2);
+    /// assert_eq!(epd.backend().pixel(505, 905), Some(Gray4::GRAY));
+    /// ```
+    pub fn update_dirty(&mut self, mode: impl Into<DrawFlags>) -> Result<(), EpdError> {
+        let mode = highlevel_mode(mode.into())?;
+        let rects = self.epd.state.fb.dirty_rects();
+        if rects.is_empty() {
+            return Ok(());
+        }
+        let temperature = self.update_temperature()?;
+        let result = rects
+            .into_iter()
+            .try_for_each(|area| self.epd.update_landscape_area(area, mode, temperature));
+        if result.is_ok() {
+            self.epd.state.fb.reset_dirty();
+        }
+        result
+    }
+}
+
+#[cfg(target_os = "espidf")]
+impl PowerSession<'_, Epdiy> {
+    /// Write text between updates, see [`Epd::write_text`].
+    pub fn write_text(
+        &mut self,
+        font: &EpdFont,
+        text: &str,
+        cursor: Cursor,
+        props: &FontProperties,
+    ) -> Result<Cursor, EpdError> {
+        self.epd.write_text(font, text, cursor, props)
+    }
+}
+
+impl<B: EpdBackend> Drop for PowerSession<'_, B> {
+    fn drop(&mut self) {
+        self.epd.state.backend.power_off();
+    }
+}
+
+/// The high level API picks the framebuffer packing and background itself,
+/// so only the draw mode can be passed to it.
+fn highlevel_mode(mode: DrawFlags) -> Result<DrawMode, EpdError> {
+    if mode.has_flags() {
+        return Err(InvalidDrawFlags::FlagsNotAllowed.into());
     }
+    Ok(mode.mode())
 }
@@ -0,0 +1,195 @@
+//! The [`EpdBackend`] of the device, using epdiy's high level API.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use epd_gfx::rect::Rect;
+use epd_gfx::{Framebuffer, Gray4};
+
+use crate::backend::{EpdBackend, Rotation, UPDATE_ROTATION};
+use crate::draw_mode::DrawMode;
+use crate::epd_highlevel::{self, EpdRect, EpdRotation, EpdWaveform, EpdiyHighlevelState};
+use crate::error::DrawError;
+use crate::init_options::InitOptions;
+use crate::temperature::TemperatureRange;
+
+const EPD_WIDTH: usize = 960;
+const EPD_HEIGHT: usize = 540;
+const FB_SIZE: usize = EPD_WIDTH / 2 * EPD_HEIGHT;
+
+/// epdiy allocates the framebuffers of the high level API only once, so
+/// there can only be one `Epdiy`.
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// The display of the board, driven by epdiy.
+#[derive(Debug)]
+pub struct Epdiy {
+    /// Set by the first `init`.
+    hl_state: Option<EpdiyHighlevelState>,
+    /// The waveform chosen by `epd_hl_init`, to return to after a custom one.
+    builtin_waveform: Option<*const EpdWaveform>,
+    framebuffer_taken: bool,
+}
+
+impl Epdiy {
+    /// The display, or `None` if it was taken before.
+    pub fn take() -> Option<Self> {
+        if TAKEN.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        Some(Self {
+            hl_state: None,
+            builtin_waveform: None,
+            framebuffer_taken: false,
+        })
+    }
+
+    fn state(&mut self) -> &mut EpdiyHighlevelState {
+        self.hl_state.as_mut().expect("epdiy is not initialized")
+    }
+}
+
+impl EpdBackend for Epdiy {
+    type Framebuffer = FrontBuffer;
+
+    fn init(&mut self, options: &InitOptions) {
+        unsafe { epd_highlevel::epd_init(options.bits()) };
+        let state = self
+            .hl_state
+            .get_or_insert_with(|| unsafe { epd_highlevel::epd_hl_init() });
+        let builtin = *self.builtin_waveform.get_or_insert(state.waveform);
+        state.waveform = options.waveform_ptr().unwrap_or(builtin);
+        set_rotation(UPDATE_ROTATION);
+    }
+
+    fn framebuffer(&mut self) -> FrontBuffer {
+        // A second `FrontBuffer` would alias the first one
+        assert!(!self.framebuffer_taken, "the framebuffer was taken before");
+        self.framebuffer_taken = true;
+        FrontBuffer {
+            ptr: self.state().front_fb,
+        }
+    }
+
+    fn deinit(&mut self) {
+        unsafe { epd_highlevel::epd_deinit() };
+    }
+
+    fn power_on(&mut self) {
+        unsafe { epd_highlevel::epd_poweron() };
+    }
+
+    fn power_off(&mut self) {
+        unsafe { epd_highlevel::epd_poweroff() };
+    }
+
+    fn clear(&mut self) {
+        unsafe { epd_highlevel::epd_clear() };
+    }
+
+    fn ambient_temperature(&mut self) -> f32 {
+        unsafe { epd_highlevel::epd_ambient_temperature() }
+    }
+
+    fn temperature_ranges(&self) -> Vec<TemperatureRange> {
+        let Some(state) = &self.hl_state else {
+            return Vec::new();
+        };
+        // SAFETY: epdiy's waveforms are immutable statics, which hold
+        // `num_temp_ranges` intervals
+        let intervals = unsafe {
+            let waveform = &*state.waveform;
+            if waveform.temp_intervals.is_null() {
+                return Vec::new();
+            }
+            std::slice::from_raw_parts(waveform.temp_intervals, waveform.num_temp_ranges as usize)
+        };
+        intervals
+            .iter()
+            .map(|interval| TemperatureRange {
+                min: interval.min,
+                max: interval.max,
+            })
+            .collect()
+    }
+
+    fn update_screen(
+        &mut self,
+        _fb: &FrontBuffer,
+        mode: DrawMode,
+        temperature: i32,
+    ) -> Result<(), DrawError> {
+        let state = self.state();
+        let result =
+            unsafe { epd_highlevel::epd_hl_update_screen(state, mode.bits(), temperature) };
+        DrawError::check(result)
+    }
+
+    fn update_area(
+        &mut self,
+        _fb: &FrontBuffer,
+        area: Rect,
+        mode: DrawMode,
+        temperature: i32,
+    ) -> Result<(), DrawError> {
+        let area = EpdRect {
+            x: area.x as i32,
+            y: area.y as i32,
+            width: area.width as i32,
+            height: area.height as i32,
+        };
+        let state = self.state();
+        let result =
+            unsafe { epd_highlevel::epd_hl_update_area(state, mode.bits(), temperature, area) };
+        DrawError::check(result)
+    }
+}
+
+/// Run `f` with epdiy in `rotation`, e.g. to draw in the portrait coordinates
+/// of epd-gfx, and return to the rotation of updates afterwards.
+pub(crate) fn with_rotation<R>(rotation: Rotation, f: impl FnOnce() -> R) -> R {
+    set_rotation(rotation);
+    let result = f();
+    set_rotation(UPDATE_ROTATION);
+    result
+}
+
+fn set_rotation(rotation: Rotation) {
+    let rotation: EpdRotation = match rotation {
+        Rotation::Landscape => 0,
+        Rotation::Portrait => 1,
+    };
+    unsafe { epd_highlevel::epd_set_rotation(rotation) };
+}
+
+/// The framebuffer of the high level API, see [`Epdiy::framebuffer`].
+#[derive(Debug)]
+pub struct FrontBuffer {
+    ptr: *mut u8,
+}
+
+impl FrontBuffer {
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: epdiy allocated FB_SIZE bytes, which are never freed and
+        // only accessed through the one `FrontBuffer`
+        unsafe { std::slice::from_raw_parts(self.ptr, FB_SIZE) }
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        // SAFETY: see `as_bytes`, the borrow of `self` makes it unique
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, FB_SIZE) }
+    }
+}
+
+impl Framebuffer for FrontBuffer {
+    fn draw_pixel(&mut self, x: u32, y: u32, color: Gray4) {
+        self.as_bytes_mut().draw_pixel(x, y, color);
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Option<Gray4> {
+        self.as_bytes().get_pixel(x, y)
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Gray4) {
+        self.as_bytes_mut().fill_rect(x, y, w, h, color);
+    }
+}
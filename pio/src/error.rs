@@ -0,0 +1,189 @@
+//! Errors of the display driver.
+//!
+//! epdiy reports drawing failures as `enum EpdDrawError`, a set of bit flags
+//! of which several can be set at once. [`DrawError`] keeps these flags and
+//! [`EpdError`] adds the failures detected on the Rust side.
+
+use std::fmt;
+
+#[cfg(target_os = "espidf")]
+use crate::draw::InvalidShape;
+use crate::draw_mode::InvalidDrawFlags;
+use crate::temperature::TemperatureOutOfRange;
+
+/// The failure flags of an `EpdDrawError` other than `EPD_DRAW_SUCCESS`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DrawError(u32);
+
+impl DrawError {
+    /// No valid framebuffer packing mode was specified.
+    pub const INVALID_PACKING_MODE: DrawError = DrawError(0x1);
+    /// No lookup table implementation for this mode and packing.
+    pub const LOOKUP_NOT_IMPLEMENTED: DrawError = DrawError(0x2);
+    /// The string to draw is invalid.
+    pub const STRING_INVALID: DrawError = DrawError(0x4);
+    /// The string was not empty, but no characters were drawable.
+    pub const NO_DRAWABLE_CHARACTERS: DrawError = DrawError(0x8);
+    /// An allocation failed.
+    pub const FAILED_ALLOC: DrawError = DrawError(0x10);
+    /// A glyph could not be drawn and there was no fallback glyph.
+    pub const GLYPH_FALLBACK_FAILED: DrawError = DrawError(0x20);
+    /// The crop area is invalid.
+    pub const INVALID_CROP: DrawError = DrawError(0x40);
+    /// The waveform has no such mode.
+    pub const MODE_NOT_FOUND: DrawError = DrawError(0x80);
+    /// The waveform has no phases for the temperature.
+    pub const NO_PHASES_AVAILABLE: DrawError = DrawError(0x100);
+    /// An invalid combination of font flags was used.
+    pub const INVALID_FONT_FLAGS: DrawError = DrawError(0x200);
+
+    const NAMES: [(DrawError, &'static str); 10] = [
+        (Self::INVALID_PACKING_MODE, "invalid packing mode"),
+        (Self::LOOKUP_NOT_IMPLEMENTED, "lookup not implemented"),
+        (Self::STRING_INVALID, "invalid string"),
+        (Self::NO_DRAWABLE_CHARACTERS, "no drawable characters"),
+        (Self::FAILED_ALLOC, "allocation failed"),
+        (Self::GLYPH_FALLBACK_FAILED, "glyph fallback failed"),
+        (Self::INVALID_CROP, "invalid crop area"),
+        (Self::MODE_NOT_FOUND, "mode not found in waveform"),
+        (Self::NO_PHASES_AVAILABLE, "no phases for temperature"),
+        (Self::INVALID_FONT_FLAGS, "invalid font flags"),
+    ];
+
+    /// `Ok` for `EPD_DRAW_SUCCESS`, the set flags otherwise.
+    /// ```
+    /// use esp_idf_rs::error::DrawError;
+    /// assert_eq!(DrawError::check(0), Ok(()));
+    ///
+    /// let e = DrawError::check(0x4 | 0x200).unwrap_err();
+    /// assert!(e.contains(DrawError::STRING_INVALID));
+    /// assert!(!e.contains(DrawError::MODE_NOT_FOUND));
+    /// let flags: Vec<_> = e.iter().collect();
+    /// assert_eq!(flags, [DrawError::STRING_INVALID, DrawError::INVALID_FONT_FLAGS]);
+    /// assert_eq!(e.to_string(), "invalid string, invalid font flags");
+    ///
+    /// let e = DrawError::check(0x80 | 0x1000).unwrap_err();
+    /// assert_eq!(e.to_string(), "mode not found in waveform, unknown error 0x1000");
+    /// ```
+    pub fn check(result: u32) -> Result<(), DrawError> {
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(DrawError(result))
+        }
+    }
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Whether all flags of `other` are set.
+    pub fn contains(self, other: DrawError) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The known flags which are set.
+    pub fn iter(self) -> impl Iterator<Item = DrawError> {
+        Self::NAMES
+            .into_iter()
+            .map(|(flag, _)| flag)
+            .filter(move |&flag| self.contains(flag))
+    }
+}
+
+impl fmt::Debug for DrawError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DrawError({:#x}: {self})", self.0)
+    }
+}
+
+impl fmt::Display for DrawError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut known = 0;
+        for (i, (flag, name)) in Self::NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .enumerate()
+        {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{name}")?;
+            known |= flag.0;
+        }
+        let unknown = self.0 & !known;
+        if unknown != 0 {
+            if known != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "unknown error {unknown:#x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DrawError {}
+
+/// Everything that can go wrong when using the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EpdError {
+    /// The draw mode can not be used for this call.
+    InvalidDrawFlags(InvalidDrawFlags),
+    /// epdiy failed to draw.
+    Draw(DrawError),
+    /// A shape or image can not be drawn by epdiy.
+    #[cfg(target_os = "espidf")]
+    InvalidShape(InvalidShape),
+    /// The waveform does not cover the temperature, see
+    /// `Temperature::set_strict`.
+    Temperature(TemperatureOutOfRange),
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EpdError::InvalidDrawFlags(e) => write!(f, "invalid draw mode: {e}"),
+            EpdError::Draw(e) => write!(f, "drawing failed: {e}"),
+            #[cfg(target_os = "espidf")]
+            EpdError::InvalidShape(e) => write!(f, "invalid shape: {e}"),
+            EpdError::Temperature(e) => write!(f, "unsupported temperature: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for EpdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EpdError::InvalidDrawFlags(e) => Some(e),
+            EpdError::Draw(e) => Some(e),
+            #[cfg(target_os = "espidf")]
+            EpdError::InvalidShape(e) => Some(e),
+            EpdError::Temperature(e) => Some(e),
+        }
+    }
+}
+
+impl From<InvalidDrawFlags> for EpdError {
+    fn from(e: InvalidDrawFlags) -> Self {
+        EpdError::InvalidDrawFlags(e)
+    }
+}
+
+impl From<DrawError> for EpdError {
+    fn from(e: DrawError) -> Self {
+        EpdError::Draw(e)
+    }
+}
+
+#[cfg(target_os = "espidf")]
+impl From<InvalidShape> for EpdError {
+    fn from(e: InvalidShape) -> Self {
+        EpdError::InvalidShape(e)
+    }
+}
+
+impl From<TemperatureOutOfRange> for EpdError {
+    fn from(e: TemperatureOutOfRange) -> Self {
+        EpdError::Temperature(e)
+    }
+}
@@ -0,0 +1,135 @@
+//! Options for initializing the display driver.
+//!
+//! The defaults favour speed. Boards without PSRAM, or applications short on
+//! internal RAM, can trade some update speed for memory with a smaller lookup
+//! table and feed queue.
+
+#[cfg(target_os = "espidf")]
+use crate::epd_highlevel::{self, EpdWaveform};
+
+/// Size of the lookup table from pixel values to display output, which is
+/// allocated in internal memory for as long as the driver runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LutSize {
+    /// 1 KiB. Saves memory, but updates take longer to prepare, and 4bpp
+    /// framebuffers can only be drawn on a uniformly white or black display.
+    Lut1K,
+    /// 64 KiB. Fastest, but permanently occupies a 64 KiB block of internal
+    /// memory.
+    #[default]
+    Lut64K,
+}
+
+/// Number of display lines buffered between preparing and sending them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FeedQueue {
+    /// 8 lines. Uses less memory, but the display may have to wait for data.
+    Lines8,
+    /// 32 lines. Fastest, with a larger memory footprint.
+    #[default]
+    Lines32,
+}
+
+/// The waveform, i.e. the voltage sequences used to change pixels, which
+/// has to match the display panel.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Waveform {
+    /// The waveform of the display type selected in the epdiy configuration.
+    #[default]
+    Builtin,
+    Ed047tc1,
+    Ed060sc4,
+    Ed060sct,
+    Ed060xc3,
+    Ed097oc4,
+    Ed097tc2,
+    Ed133ut2,
+    /// A waveform created for a specific panel, e.g. from vendor files.
+    #[cfg(target_os = "espidf")]
+    Custom(&'static EpdWaveform),
+}
+
+#[cfg(target_os = "espidf")]
+impl Waveform {
+    /// The waveform to use instead of the builtin one, if any.
+    pub(crate) fn as_ptr(self) -> Option<*const EpdWaveform> {
+        // SAFETY: the waveforms are immutable statics of epdiy
+        let waveform = unsafe {
+            match self {
+                Waveform::Builtin => return None,
+                Waveform::Ed047tc1 => &epd_highlevel::epdiy_ED047TC1,
+                Waveform::Ed060sc4 => &epd_highlevel::epdiy_ED060SC4,
+                Waveform::Ed060sct => &epd_highlevel::epdiy_ED060SCT,
+                Waveform::Ed060xc3 => &epd_highlevel::epdiy_ED060XC3,
+                Waveform::Ed097oc4 => &epd_highlevel::epdiy_ED097OC4,
+                Waveform::Ed097tc2 => &epd_highlevel::epdiy_ED097TC2,
+                Waveform::Ed133ut2 => &epd_highlevel::epdiy_ED133UT2,
+                Waveform::Custom(waveform) => waveform,
+            }
+        };
+        Some(waveform as *const EpdWaveform)
+    }
+}
+
+/// How to initialize the driver, see [`crate::epd::Epd::init`].
+///
+/// The default uses the 64 KiB lookup table, the 32 line feed queue and the
+/// builtin waveform:
+/// ```ignore
+/// let options = InitOptions::new()
+///     .lut(LutSize::Lut1K)
+///     .feed_queue(FeedQueue::Lines8);
+/// epd.init(options);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InitOptions {
+    lut: LutSize,
+    feed_queue: FeedQueue,
+    waveform: Waveform,
+}
+
+impl InitOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The smallest footprint in internal memory.
+    pub fn low_memory() -> Self {
+        Self::new()
+            .lut(LutSize::Lut1K)
+            .feed_queue(FeedQueue::Lines8)
+    }
+
+    pub fn lut(mut self, lut: LutSize) -> Self {
+        self.lut = lut;
+        self
+    }
+
+    pub fn feed_queue(mut self, feed_queue: FeedQueue) -> Self {
+        self.feed_queue = feed_queue;
+        self
+    }
+
+    pub fn waveform(mut self, waveform: Waveform) -> Self {
+        self.waveform = waveform;
+        self
+    }
+
+    #[cfg(target_os = "espidf")]
+    pub(crate) fn waveform_ptr(&self) -> Option<*const EpdWaveform> {
+        self.waveform.as_ptr()
+    }
+
+    /// The value to pass to `epd_init`.
+    pub fn bits(&self) -> u32 {
+        let lut = match self.lut {
+            LutSize::Lut1K => 0x1,
+            LutSize::Lut64K => 0x2,
+        };
+        let feed_queue = match self.feed_queue {
+            FeedQueue::Lines8 => 0x4,
+            FeedQueue::Lines32 => 0x8,
+        };
+        lut | feed_queue
+    }
+}
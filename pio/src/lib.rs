@@ -1,32 +1,54 @@
-use epd_gfx;
-
+#[cfg(target_os = "espidf")]
 use esp_idf_sys::{vTaskDelay, TickType_t};
 
+pub mod backend;
+#[cfg(target_os = "espidf")]
+pub mod draw;
+pub mod draw_mode;
 pub mod epd;
+#[cfg(target_os = "espidf")]
 pub mod epd_highlevel;
+#[cfg(target_os = "espidf")]
+pub mod epdiy;
+pub mod error;
+#[cfg(target_os = "espidf")]
 pub mod firasans;
+pub mod init_options;
+pub mod simulator;
+pub mod temperature;
+pub mod text;
 
+#[cfg(target_os = "espidf")]
 unsafe fn delay() {
     //https://github.com/espressif/esp-idf/issues/1646#issuecomment-913190625
     let delay: TickType_t = 500;
     vTaskDelay(delay);
 }
 
+#[cfg(target_os = "espidf")]
 #[no_mangle]
 extern "C" fn app_main() {
     println!("initializing...");
-    let mut epd = epd::Epd::new();
-    epd.init();
+    let epd = epd::Epd::take().unwrap();
+    let mut epd = epd.init(init_options::InitOptions::new());
     epd.clear();
 
     println!("drawing...");
-    let mut fb = epd.get_framebuffer().unwrap();
-    epd_gfx::set_all(&mut fb, 0xFF);
-    epd_gfx::fill_rect(&mut fb, 50, 75, 400, 250, 0x0);
-    epd_gfx::fill_rect(&mut fb, 200, 150, 400, 250, 0x8);
-    epd_gfx::font::draw_text(&mut fb, 0, 0, "Hello from RustType!", 32);
+    let fb = epd.framebuffer_mut();
+    epd_gfx::fill_rect(fb, 0, 0, 540, 960, epd_gfx::Gray4::WHITE);
+    epd_gfx::fill_rect(fb, 50, 75, 400, 250, epd_gfx::Gray4::BLACK);
+    epd_gfx::fill_rect(fb, 200, 150, 400, 250, epd_gfx::Gray4::GRAY);
+    epd_gfx::font::draw_text(fb, 0, 0, "Hello from RustType!", 32);
+
+    let props = text::FontProperties::new().alignment(text::Alignment::Center);
+    let cursor = text::Cursor::new(270, 600);
+    if let Err(e) = epd.write_text(&firasans::FiraSans_12, "Hello from epdiy!", cursor, &props) {
+        println!("writing text failed: {e}");
+    }
 
-    epd.update_screen(25i32);
+    if let Err(e) = epd.update_screen(draw_mode::DrawMode::Gc16) {
+        println!("update failed: {e}");
+    }
 
     println!("looping...");
     loop {
@@ -0,0 +1,225 @@
+//! An [`EpdBackend`] which keeps the display in memory, to run and test
+//! application code on the host.
+//!
+//! The simulator checks that it is used like the real display, e.g. that
+//! the power is on during updates, and panics otherwise.
+//! ```
+//! use epd_gfx::Gray4;
+//! use esp_idf_rs::draw_mode::DrawMode;
+//! use esp_idf_rs::epd::Epd;
+//! use esp_idf_rs::init_options::InitOptions;
+//! use esp_idf_rs::simulator::Simulator;
+//!
+//! let mut epd = Epd::new(Simulator::new()).init(InitOptions::new());
+//! epd_gfx::fill_rect(epd.framebuffer_mut(), 10, 20, 30, 40, Gray4::BLACK);
+//! epd.update_dirty(DrawMode::Gc16).unwrap();
+//!
+//! let display = epd.backend();
+//! assert_eq!(display.pixel(15, 25), Some(Gray4::BLACK));
+//! assert_eq!(display.pixel(5, 5), Some(Gray4::WHITE));
+//! assert_eq!(display.updates().len(), 1);
+//! assert!(!display.is_powered());
+//! ```
+
+use epd_gfx::rect::Rect;
+use epd_gfx::{Framebuffer, Gray4};
+
+use crate::backend::{EpdBackend, Rotation, UPDATE_ROTATION};
+use crate::draw_mode::DrawMode;
+use crate::error::DrawError;
+use crate::init_options::InitOptions;
+use crate::temperature::TemperatureRange;
+
+const FB_SIZE: usize = (epd_gfx::EPD_WIDTH / 2 * epd_gfx::EPD_HEIGHT) as usize;
+
+/// An update of the simulated display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Update {
+    /// The updated area in landscape coordinates, the whole display for
+    /// [`EpdBackend::update_screen`].
+    pub area: Rect,
+    pub mode: DrawMode,
+    pub temperature: i32,
+}
+
+/// A display in memory, which shows what the real one would.
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    /// The 4bpp contents of the display, in the layout of the framebuffer.
+    screen: Vec<u8>,
+    initialized: bool,
+    framebuffer_taken: bool,
+    powered: bool,
+    /// Applied to the areas of updates, like epdiy does.
+    rotation: Rotation,
+    temperature: f32,
+    temperature_ranges: Vec<TemperatureRange>,
+    updates: Vec<Update>,
+}
+
+impl Default for Simulator {
+    /// A white display at room temperature.
+    fn default() -> Self {
+        Self {
+            screen: vec![0xFF; FB_SIZE],
+            initialized: false,
+            framebuffer_taken: false,
+            powered: false,
+            rotation: UPDATE_ROTATION,
+            temperature: 22.0,
+            temperature_ranges: vec![TemperatureRange { min: 0, max: 50 }],
+            updates: Vec::new(),
+        }
+    }
+}
+
+impl Simulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The temperature reported by the simulated sensor.
+    pub fn set_temperature(&mut self, celsius: f32) {
+        self.temperature = celsius;
+    }
+
+    /// The temperature ranges of the simulated waveform.
+    pub fn set_temperature_ranges(&mut self, ranges: Vec<TemperatureRange>) {
+        self.temperature_ranges = ranges;
+    }
+
+    /// What the display shows, in the layout of the framebuffer.
+    pub fn screen(&self) -> &[u8] {
+        &self.screen
+    }
+
+    /// The color shown at (x, y), in portrait coordinates.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<Gray4> {
+        self.screen.get_pixel(x, y)
+    }
+
+    /// All updates so far, oldest first.
+    pub fn updates(&self) -> &[Update] {
+        &self.updates
+    }
+
+    pub fn is_powered(&self) -> bool {
+        self.powered
+    }
+
+    fn check_ready(&self, what: &str) {
+        assert!(
+            self.initialized,
+            "{what} before the display was initialized"
+        );
+        assert!(self.powered, "{what} without power");
+    }
+
+    /// Show `area` of `fb` the way `mode` draws it. Every mode is accepted, as
+    /// a custom waveform may provide it.
+    fn show(&mut self, fb: &[u8], area: Rect, mode: DrawMode) {
+        let levels = match mode {
+            DrawMode::Du | DrawMode::A2 | DrawMode::Monochrome => 2,
+            DrawMode::Du4 | DrawMode::Gl4 => 4,
+            DrawMode::Gc16
+            | DrawMode::Gc16Fast
+            | DrawMode::Gl16
+            | DrawMode::Gl16Fast
+            | DrawMode::Gl16Inv
+            | DrawMode::WhiteToGl16
+            | DrawMode::BlackToGl16 => 16,
+        };
+        let Some(area) = self.rotation.panel_area(area) else {
+            return;
+        };
+        for y in area.y..area.bottom() {
+            for x in area.x..area.right() {
+                let Some((x, y)) = epd_gfx::from_landscape(x, y) else {
+                    continue;
+                };
+                let Some(color) = fb.get_pixel(x, y) else {
+                    continue;
+                };
+                self.screen.draw_pixel(x, y, reduce(color, levels));
+            }
+        }
+    }
+}
+
+/// The closest of `levels` evenly spaced gray levels to `color`.
+fn reduce(color: Gray4, levels: u8) -> Gray4 {
+    let step = 15 / (levels - 1);
+    Gray4::new((color.level() + step / 2) / step * step)
+}
+
+impl EpdBackend for Simulator {
+    type Framebuffer = Vec<u8>;
+
+    fn init(&mut self, _options: &InitOptions) {
+        self.initialized = true;
+        self.rotation = UPDATE_ROTATION;
+    }
+
+    fn framebuffer(&mut self) -> Vec<u8> {
+        assert!(!self.framebuffer_taken, "the framebuffer was taken before");
+        self.framebuffer_taken = true;
+        vec![0xFF; FB_SIZE]
+    }
+
+    fn deinit(&mut self) {
+        self.initialized = false;
+        self.powered = false;
+    }
+
+    fn power_on(&mut self) {
+        assert!(
+            self.initialized,
+            "power on before the display was initialized"
+        );
+        self.powered = true;
+    }
+
+    fn power_off(&mut self) {
+        self.powered = false;
+    }
+
+    fn clear(&mut self) {
+        self.check_ready("clear");
+        self.screen.fill(0xFF);
+    }
+
+    fn ambient_temperature(&mut self) -> f32 {
+        self.check_ready("reading the temperature");
+        self.temperature
+    }
+
+    fn temperature_ranges(&self) -> Vec<TemperatureRange> {
+        self.temperature_ranges.clone()
+    }
+
+    fn update_screen(
+        &mut self,
+        fb: &Vec<u8>,
+        mode: DrawMode,
+        temperature: i32,
+    ) -> Result<(), DrawError> {
+        self.update_area(fb, Rect::landscape(), mode, temperature)
+    }
+
+    fn update_area(
+        &mut self,
+        fb: &Vec<u8>,
+        area: Rect,
+        mode: DrawMode,
+        temperature: i32,
+    ) -> Result<(), DrawError> {
+        self.check_ready("update");
+        self.show(fb, area, mode);
+        self.updates.push(Update {
+            area,
+            mode,
+            temperature,
+        });
+        Ok(())
+    }
+}
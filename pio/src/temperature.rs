@@ -0,0 +1,236 @@
+//! Ambient temperature for selecting the waveform phases.
+//!
+//! E-paper pixels switch slower in the cold, so waveforms contain phase
+//! tables for several temperature ranges. [`Temperature`] reads the board's
+//! sensor, reuses readings for a while since reading takes time, and smooths
+//! them so a single noisy reading does not switch ranges.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::backend::EpdBackend;
+
+/// How long a reading is used before the sensor is read again.
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(60);
+
+/// Weight of a new reading in the running average, from 0.0 to 1.0.
+const DEFAULT_SMOOTHING: f32 = 0.5;
+
+/// A range of temperatures of the waveform, in degrees Celsius.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemperatureRange {
+    pub min: i32,
+    pub max: i32,
+}
+
+/// A temperature and the waveform range selected for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemperatureReading {
+    /// Degrees Celsius, as passed to epdiy.
+    pub celsius: i32,
+    /// Index of the waveform range epdiy uses for this temperature.
+    pub range: usize,
+    /// Set if `celsius` lies outside of the waveform's ranges, in which case
+    /// the closest range is used, which may leave ghosting.
+    pub out_of_range: Option<TemperatureOutOfRange>,
+}
+
+/// The temperature is not covered by the waveform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemperatureOutOfRange {
+    pub celsius: i32,
+    /// The lowest temperature of the waveform.
+    pub min: i32,
+    /// The highest temperature of the waveform.
+    pub max: i32,
+}
+
+impl fmt::Display for TemperatureOutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}°C is outside of the waveform's range of {}°C to {}°C",
+            self.celsius, self.min, self.max
+        )
+    }
+}
+
+impl std::error::Error for TemperatureOutOfRange {}
+
+/// Source of the temperature used for display updates.
+pub struct Temperature {
+    /// Read instead of the sensor of the backend, if set.
+    sensor: Option<fn() -> f32>,
+    hook: Option<Box<dyn FnMut() -> Option<f32>>>,
+    max_age: Duration,
+    smoothing: f32,
+    strict: bool,
+    /// The smoothed temperature and when the sensor was last read.
+    cache: Option<(f32, Instant)>,
+}
+
+impl fmt::Debug for Temperature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Temperature")
+            .field("sensor", &self.sensor.is_some())
+            .field("hook", &self.hook.is_some())
+            .field("max_age", &self.max_age)
+            .field("smoothing", &self.smoothing)
+            .field("strict", &self.strict)
+            .field("cache", &self.cache)
+            .finish()
+    }
+}
+
+impl Default for Temperature {
+    /// The sensor of the board, see [`EpdBackend::ambient_temperature`].
+    fn default() -> Self {
+        Self {
+            sensor: None,
+            hook: None,
+            max_age: DEFAULT_MAX_AGE,
+            smoothing: DEFAULT_SMOOTHING,
+            strict: false,
+            cache: None,
+        }
+    }
+}
+
+impl Temperature {
+    /// Read temperatures from `sensor` instead of the board's sensor.
+    pub fn new(sensor: fn() -> f32) -> Self {
+        Self {
+            sensor: Some(sensor),
+            ..Self::default()
+        }
+    }
+
+    /// Ask `hook` for the temperature first, e.g. to use a better sensor or
+    /// a fixed value. If it returns `None`, the sensor is read as usual.
+    /// Temperatures from the hook are used as they are.
+    pub fn set_override(&mut self, hook: impl FnMut() -> Option<f32> + 'static) {
+        self.hook = Some(Box::new(hook));
+    }
+
+    pub fn clear_override(&mut self) {
+        self.hook = None;
+    }
+
+    /// How long a reading of the sensor is reused.
+    pub fn set_max_age(&mut self, max_age: Duration) {
+        self.max_age = max_age;
+    }
+
+    /// Weight of a new reading in the running average, from 0.0 to 1.0.
+    /// 1.0 disables smoothing.
+    pub fn set_smoothing(&mut self, smoothing: f32) {
+        self.smoothing = smoothing.clamp(0.0, 1.0);
+    }
+
+    /// Fail updates if the temperature is outside of the waveform's ranges,
+    /// instead of using the closest range.
+    /// ```
+    /// use esp_idf_rs::draw_mode::DrawMode;
+    /// use esp_idf_rs::epd::Epd;
+    /// use esp_idf_rs::error::EpdError;
+    /// use esp_idf_rs::init_options::InitOptions;
+    /// use esp_idf_rs::simulator::Simulator;
+    ///
+    /// let mut display = Simulator::new();
+    /// display.set_temperature(60.0);
+    /// let mut epd = Epd::new(display).init(InitOptions::new());
+    /// epd.update_screen(DrawMode::Gc16).unwrap();
+    ///
+    /// epd.temperature_source().set_strict(true);
+    /// let result = epd.update_screen(DrawMode::Gc16);
+    /// assert!(matches!(result, Err(EpdError::Temperature(e)) if e.celsius == 60));
+    /// assert_eq!(epd.backend().updates().len(), 1);
+    /// ```
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Forget previous readings, so the next one is taken from the sensor.
+    pub fn invalidate(&mut self) {
+        self.cache = None;
+    }
+
+    /// The current temperature in degrees Celsius. The board's sensor is
+    /// read through `backend`, which has to be powered on.
+    pub fn celsius(&mut self, backend: &mut impl EpdBackend) -> f32 {
+        if let Some(celsius) = self.hook.as_mut().and_then(|hook| hook()) {
+            return celsius;
+        }
+        let now = Instant::now();
+        match self.cache {
+            Some((celsius, read_at)) if now.duration_since(read_at) < self.max_age => celsius,
+            cache => {
+                let reading = match self.sensor {
+                    Some(sensor) => sensor(),
+                    None => backend.ambient_temperature(),
+                };
+                let celsius = match cache {
+                    Some((average, _)) => average + (reading - average) * self.smoothing,
+                    None => reading,
+                };
+                self.cache = Some((celsius, now));
+                celsius
+            }
+        }
+    }
+
+    /// The current temperature and the range of the backend's waveform epdiy
+    /// selects for it.
+    pub fn read(&mut self, backend: &mut impl EpdBackend) -> TemperatureReading {
+        let celsius = self.celsius(backend).round() as i32;
+        let ranges = backend.temperature_ranges();
+        let (range, out_of_range) = match waveform_range(&ranges, celsius) {
+            Ok(range) => (range, None),
+            Err(e) if celsius < e.min => (0, Some(e)),
+            Err(e) => (ranges.len().saturating_sub(1), Some(e)),
+        };
+        TemperatureReading {
+            celsius,
+            range,
+            out_of_range,
+        }
+    }
+}
+
+/// The index in `ranges` of the waveform for `celsius`, chosen like
+/// epdiy's `waveform_temp_range_index`. Without any ranges, e.g. when the
+/// waveform is not known, the temperature is not checked.
+/// ```
+/// use esp_idf_rs::temperature::{waveform_range, TemperatureRange};
+/// let ranges = [
+///     TemperatureRange { min: 0, max: 20 },
+///     TemperatureRange { min: 20, max: 50 },
+/// ];
+/// assert_eq!(waveform_range(&ranges, 25), Ok(1));
+/// assert_eq!(waveform_range(&ranges, 60).unwrap_err().max, 50);
+/// assert_eq!(waveform_range(&[], 60), Ok(0));
+/// ```
+pub fn waveform_range(
+    ranges: &[TemperatureRange],
+    celsius: i32,
+) -> Result<usize, TemperatureOutOfRange> {
+    let (Some(first), Some(last)) = (ranges.first(), ranges.last()) else {
+        return Ok(0);
+    };
+    if celsius < first.min || celsius > last.max {
+        return Err(TemperatureOutOfRange {
+            celsius,
+            min: first.min,
+            max: last.max,
+        });
+    }
+    let mut index = 0;
+    while index < ranges.len() - 1 && ranges[index].min < celsius {
+        index += 1;
+    }
+    Ok(index)
+}
@@ -0,0 +1,319 @@
+//! Text drawn by epdiy with its compiled fonts, see [`crate::firasans`].
+//!
+//! epdiy draws text into the framebuffer like epd-gfx does, in the portrait
+//! coordinates of the display. The y coordinate of a cursor is the baseline
+//! of the line, so text drawn at y = 0 is mostly cut off.
+//!
+//! Only the drawing needs epdiy, the properties and bounds of text are also
+//! available on the host.
+
+#[cfg(target_os = "espidf")]
+use std::ffi::CString;
+
+use epd_gfx::rect::Rect;
+use epd_gfx::Gray4;
+
+#[cfg(target_os = "espidf")]
+use crate::{
+    backend::Rotation,
+    epd_highlevel::{self, EpdFont, EpdFontFlags, EpdFontProperties},
+    epdiy::with_rotation,
+    error::DrawError,
+};
+
+#[cfg(target_os = "espidf")]
+const DRAW_BACKGROUND: EpdFontFlags = 0x1;
+
+/// How the lines of a text are placed relative to the cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Alignment {
+    /// Lines start at the cursor.
+    #[default]
+    Left,
+    /// Lines are centered on the cursor.
+    Center,
+    /// Lines end at the cursor.
+    Right,
+}
+
+#[cfg(target_os = "espidf")]
+impl Alignment {
+    const fn bits(self) -> EpdFontFlags {
+        match self {
+            Alignment::Left => 0x2,
+            Alignment::Right => 0x4,
+            Alignment::Center => 0x8,
+        }
+    }
+}
+
+/// How to draw text, see `EpdFontProperties`.
+///
+/// The default draws black, left aligned text without a background:
+/// ```ignore
+/// let props = FontProperties::new()
+///     .alignment(Alignment::Center)
+///     .fill_background(true);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FontProperties {
+    foreground: Gray4,
+    background: Gray4,
+    fill_background: bool,
+    fallback_glyph: Option<char>,
+    alignment: Alignment,
+}
+
+impl Default for FontProperties {
+    fn default() -> Self {
+        Self {
+            foreground: Gray4::BLACK,
+            background: Gray4::WHITE,
+            fill_background: false,
+            fallback_glyph: None,
+            alignment: Alignment::Left,
+        }
+    }
+}
+
+impl FontProperties {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The color of the glyphs.
+    pub fn foreground(mut self, color: Gray4) -> Self {
+        self.foreground = color;
+        self
+    }
+
+    /// The color the edges of the glyphs are blended with, which should match
+    /// what is behind the text.
+    pub fn background(mut self, color: Gray4) -> Self {
+        self.background = color;
+        self
+    }
+
+    /// Fill the lines with the background color before drawing them.
+    pub fn fill_background(mut self, fill: bool) -> Self {
+        self.fill_background = fill;
+        self
+    }
+
+    /// Draw the glyph of `glyph` for characters missing in the font, instead
+    /// of failing with `GLYPH_FALLBACK_FAILED`.
+    pub fn fallback_glyph(mut self, glyph: char) -> Self {
+        self.fallback_glyph = Some(glyph);
+        self
+    }
+
+    pub fn alignment(mut self, alignment: Alignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+}
+
+#[cfg(target_os = "espidf")]
+impl FontProperties {
+    /// The properties to pass to epdiy.
+    pub fn to_epd(&self) -> EpdFontProperties {
+        let mut props = unsafe { epd_highlevel::epd_font_properties_default() };
+        props.set_fg_color(self.foreground.level());
+        props.set_bg_color(self.background.level());
+        props.fallback_glyph = self.fallback_glyph.map_or(0, u32::from);
+        props.flags = self.alignment.bits();
+        if self.fill_background {
+            props.flags |= DRAW_BACKGROUND;
+        }
+        props
+    }
+}
+
+/// A position in the text, with `y` on the baseline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Cursor {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Cursor {
+    pub fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+}
+
+/// The area covered by a text, which may lie partly outside of the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct TextBounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl TextBounds {
+    /// Bounds measured by `epd_get_text_bounds` for a line at `cursor`.
+    /// epdiy measures the glyphs mirrored at the baseline, so they are
+    /// flipped back to where `epd_write_string` draws them.
+    /// ```
+    /// use esp_idf_rs::text::{Cursor, TextBounds};
+    /// // A glyph 20 pixels high, of which 5 are below the baseline
+    /// let cursor = Cursor::new(10, 100);
+    /// let bounds = TextBounds::from_epdiy(cursor, 10, 95, 12, 20);
+    /// assert_eq!((bounds.y, bounds.height), (85, 20));
+    ///
+    /// let rect = bounds.to_rect().unwrap();
+    /// assert_eq!(cursor.y as u32 - rect.y, 15);
+    /// assert_eq!(rect.bottom() - cursor.y as u32, 5);
+    /// ```
+    pub fn from_epdiy(cursor: Cursor, x: i32, y: i32, width: i32, height: i32) -> TextBounds {
+        TextBounds {
+            x,
+            y: 2 * cursor.y - y - height,
+            width,
+            height,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+
+    /// The smallest bounds containing both.
+    pub fn union(&self, other: &TextBounds) -> TextBounds {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        TextBounds {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+
+    /// The visible part of the bounds, in portrait coordinates.
+    pub fn to_rect(&self) -> Option<Rect> {
+        if self.is_empty() {
+            return None;
+        }
+        let x = self.x.max(0);
+        let y = self.y.max(0);
+        let right = self.x.saturating_add(self.width);
+        let bottom = self.y.saturating_add(self.height);
+        if right <= x || bottom <= y {
+            return None;
+        }
+        let rect = Rect::new(x as u32, y as u32, (right - x) as u32, (bottom - y) as u32);
+        rect.intersection(&Rect::portrait())
+    }
+}
+
+#[cfg(target_os = "espidf")]
+/// The area `text` covers when drawn at `cursor`, including the alignment and
+/// background of `props`. Unlike `epd_get_text_bounds`, this measures every
+/// line of the text.
+pub fn text_bounds(
+    font: &EpdFont,
+    text: &str,
+    cursor: Cursor,
+    props: &FontProperties,
+) -> Result<TextBounds, DrawError> {
+    let epd_props = props.to_epd();
+    let mut bounds = TextBounds {
+        x: cursor.x,
+        y: cursor.y,
+        width: 0,
+        height: 0,
+    };
+    let mut y = cursor.y;
+    for line in text.split('\n') {
+        let line = to_c_string(line)?;
+        let line_bounds = line_bounds(font, &line, Cursor::new(cursor.x, y), props, &epd_props);
+        bounds = bounds.union(&line_bounds);
+        y += i32::from(font.advance_y);
+    }
+    Ok(bounds)
+}
+
+#[cfg(target_os = "espidf")]
+/// The bounds of a single line as epdiy draws it, which shifts the line by
+/// the width measured by `epd_get_text_bounds`.
+fn line_bounds(
+    font: &EpdFont,
+    line: &CString,
+    cursor: Cursor,
+    props: &FontProperties,
+    epd_props: &EpdFontProperties,
+) -> TextBounds {
+    let (mut x1, mut y1, mut w, mut h) = (0, 0, 0, 0);
+    unsafe {
+        epd_highlevel::epd_get_text_bounds(
+            font,
+            line.as_ptr(),
+            &cursor.x,
+            &cursor.y,
+            &mut x1,
+            &mut y1,
+            &mut w,
+            &mut h,
+            epd_props,
+        )
+    };
+    let shift = match props.alignment {
+        Alignment::Left => 0,
+        Alignment::Center => w / 2,
+        Alignment::Right => w,
+    };
+    let glyphs = TextBounds::from_epdiy(cursor, x1 - shift, y1, w, h);
+    if !props.fill_background || glyphs.is_empty() {
+        return glyphs;
+    }
+    glyphs.union(&TextBounds {
+        x: cursor.x - shift,
+        y: cursor.y - font.ascender,
+        width: w,
+        height: font.ascender - font.descender,
+    })
+}
+
+#[cfg(target_os = "espidf")]
+/// Write `text` into the 4bpp framebuffer `fb` of the display, starting at
+/// `cursor`. Returns where the cursor ended up: after the last character in
+/// x, and one line below the last line in y, as epdiy advances it.
+pub(crate) fn write_text(
+    fb: &mut [u8],
+    font: &EpdFont,
+    text: &str,
+    cursor: Cursor,
+    props: &FontProperties,
+) -> Result<Cursor, DrawError> {
+    let text = to_c_string(text)?;
+    let props = props.to_epd();
+    let mut cursor = cursor;
+    let result = with_rotation(Rotation::Portrait, || unsafe {
+        epd_highlevel::epd_write_string(
+            font,
+            text.as_ptr(),
+            &mut cursor.x,
+            &mut cursor.y,
+            fb.as_mut_ptr(),
+            &props,
+        )
+    });
+    DrawError::check(result)?;
+    Ok(cursor)
+}
+
+#[cfg(target_os = "espidf")]
+/// C strings end at the first NUL, which would silently cut off the text.
+fn to_c_string(text: &str) -> Result<CString, DrawError> {
+    CString::new(text).map_err(|_| DrawError::STRING_INVALID)
+}